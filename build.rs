@@ -0,0 +1,16 @@
+use std::process::Command;
+
+/// Embed the git commit in the version (if built from a checkout)
+fn main() {
+    let commit = Command::new("git")
+        .args(["rev-parse", "--short", "HEAD"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|hash| format!(" ({})", hash.trim()))
+        .unwrap_or_default();
+    println!("cargo:rustc-env=NIGHTRIDE_BUILD_INFO={}", commit);
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
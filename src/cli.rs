@@ -0,0 +1,99 @@
+use clap::{Parser, Subcommand};
+
+use crate::{config::Config, MAX_VOLUME};
+
+/// The crate version and the commit it was built from (if known)
+const VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), env!("NIGHTRIDE_BUILD_INFO"));
+
+#[derive(Parser)]
+#[command(about = "Nightride FM - The Home of Synthwave", version = VERSION)]
+pub struct Cli {
+    /// Station to play (name or number as used by the number keys)
+    #[arg(long)]
+    pub station: Option<String>,
+    /// Volume to play at (0-150)
+    #[arg(long, value_parser = parse_volume)]
+    pub volume: Option<f32>,
+    /// Start playback in the background and exit without showing the TUI
+    #[arg(long)]
+    pub no_ui: bool,
+    /// Show everything on a single line (toggle with `C`)
+    #[arg(long)]
+    pub compact: bool,
+    /// Instead of the TUI, print the state as a line of JSON on every sync with the player
+    #[arg(long, conflicts_with = "no_ui")]
+    pub json_status: bool,
+    /// Print the stations (number, name and title separated by tabs) and exit
+    #[arg(long)]
+    pub list_stations: bool,
+    /// Write debug output to ~/.local/share/nightride/log.txt (see also `RUST_LOG`)
+    #[arg(long)]
+    pub verbose: bool,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Commands controlling the player started by another instance
+#[derive(Subcommand)]
+pub enum Command {
+    /// Resume playback
+    Play,
+    /// Pause playback
+    Pause,
+    /// Toggle between play and pause
+    Toggle,
+    /// Switch to the next station
+    Next,
+    /// Switch to the previous station
+    Previous,
+    /// Change the volume by (e.g. `+5`, `-5`) or to (e.g. `70`) the given value
+    Volume {
+        #[arg(allow_hyphen_values = true, value_parser = parse_volume_change)]
+        volume: VolumeChange,
+    },
+    /// Print the current track (e.g. for status bars)
+    ///
+    /// Exits with 1 if no player is running and with 2 if the track is unknown.
+    NowPlaying {
+        /// Template with `{title}`, `{artist}` and `{album}` placeholders
+        #[arg(long, default_value = "{title} — {artist}")]
+        format: String,
+        /// Printed if the track is unknown
+        #[arg(long, default_value = "")]
+        placeholder: String,
+    },
+}
+
+#[derive(Clone, Copy)]
+pub enum VolumeChange {
+    By(f32),
+    To(f32),
+}
+
+pub fn parse_volume_change(s: &str) -> Result<VolumeChange, String> {
+    if s.starts_with('+') || s.starts_with('-') {
+        let change = s.parse().map_err(|_| format!("`{}` is not a number", s))?;
+        Ok(VolumeChange::By(change))
+    } else {
+        parse_volume(s).map(VolumeChange::To)
+    }
+}
+
+fn parse_volume(s: &str) -> Result<f32, String> {
+    let volume: f32 = s.parse().map_err(|_| format!("`{}` is not a number", s))?;
+    match (0.0..=MAX_VOLUME).contains(&volume) {
+        true => Ok(volume),
+        false => Err(format!("volume must be between 0 and {}", MAX_VOLUME)),
+    }
+}
+
+/// Find a station by its name or (1-based) number
+pub fn resolve_station(config: &Config, station: &str) -> Result<usize, String> {
+    let index = match station.parse::<usize>() {
+        Ok(number) => number.checked_sub(1),
+        Err(_) => config.stations.iter().position(|s| s.name == station),
+    };
+    index
+        .filter(|&index| index < config.stations.len())
+        .ok_or_else(|| format!("unknown station `{}`", station))
+}
@@ -0,0 +1,382 @@
+use home::home_dir;
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fmt::Display,
+    path::PathBuf,
+    sync::mpsc::{self, Receiver},
+    time::Duration,
+};
+
+use crate::{keymap::Keymap, lastfm, theme::Theme, write_atomically, Result};
+
+const DEFAULT_STATION_BASE_URL: &str = "http://stream.nightride.fm/";
+const MIN_POLLING_RATE_MS: u64 = 100; // Be easy on the IPC
+                                      // (name, title, description)
+const DEFAULT_STATIONS: [(&str, &str, &str); 7] = [
+    (
+        "nightride",
+        "Nightride FM",
+        "synthwave, retrowave and outrun",
+    ),
+    (
+        "chillsynth",
+        "ChillSynth",
+        "relaxed synthwave and chillwave",
+    ),
+    ("datawave", "Datawave", "glitchy retro computing vibes"),
+    ("spacesynth", "SpaceSynth", "space disco and italo"),
+    ("darksynth", "DarkSynth", "cyberpunk and synthmetal"),
+    ("horrorsynth", "HorrorSynth", "horror synth and witch house"),
+    ("ebsm", "EBSM", "electronic body music and industrial"),
+];
+const USER_CONFIG_FILE_PATH: &str = ".config/nightride/config.toml"; // relative to home dir
+const CONFIG_FILE_PATH: &str = "nightride/config.toml"; // relative to $XDG_CONFIG_HOME
+const USER_STATIONS_CACHE_FILE_PATH: &str = ".local/share/nightride/stations.json"; // relative to home dir
+const STATIONS_FETCH_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Codec of the streams (not every mpv build supports all of them)
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamFormat {
+    #[default]
+    Ogg,
+    Mp3,
+    Aac,
+}
+
+impl StreamFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            StreamFormat::Ogg => "ogg",
+            StreamFormat::Mp3 => "mp3",
+            StreamFormat::Aac => "m4a",
+        }
+    }
+}
+
+/// Range of the volume shown and set in the UI
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VolumeScale {
+    /// mpv's range (0-150)
+    #[default]
+    Raw,
+    /// 0-100, going beyond requires the boost to be enabled
+    Percent,
+}
+
+/// Where to serve the control API (requires the `http` feature)
+#[derive(Clone, Debug, Deserialize)]
+#[cfg_attr(not(feature = "http"), allow(dead_code))]
+pub struct HttpServer {
+    pub port: u16,
+    /// Only reachable from this machine unless set to e.g. `0.0.0.0`
+    #[serde(default = "default_http_address")]
+    pub address: String,
+    /// Required as `Authorization: Bearer <token>` if set (and to listen beyond this machine)
+    pub token: Option<String>,
+}
+
+fn default_http_address() -> String {
+    "127.0.0.1".into()
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Station {
+    pub name: String,
+    /// Stream URL (defaults to `<base_url><name>.<format>`)
+    pub url: Option<String>,
+    /// Overrides the global format (unless the URL is given)
+    pub format: Option<StreamFormat>,
+    /// Human-readable name (defaults to the name)
+    pub title: Option<String>,
+    pub description: Option<String>,
+    /// Image file or URL shown instead of the banner (requires the `logo` feature)
+    #[cfg_attr(not(feature = "logo"), allow(dead_code))]
+    pub logo: Option<String>,
+}
+
+impl Display for Station {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.title.as_ref().unwrap_or(&self.name))?;
+        if let Some(description) = &self.description {
+            write!(f, " — {}", description)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub base_url: String,
+    /// Format of the streams built from the base URL
+    pub format: StreamFormat,
+    pub stations: Vec<Station>,
+    /// JSON list of stations (like `stations`) used instead if it can be fetched or was cached
+    pub stations_url: Option<String>,
+    /// Scrobbling is enabled if credentials are given
+    pub lastfm: Option<lastfm::Credentials>,
+    /// Expose an MPRIS interface so media keys control playback
+    pub mpris: bool,
+    /// Pause playback when the system goes to sleep (Linux only)
+    pub pause_on_sleep: bool,
+    /// Pause playback while the audio device is gone, e.g. headphones are unplugged (Linux only)
+    pub pause_on_sink_removal: bool,
+    /// Pause playback while the terminal isn't focused
+    pub pause_on_focus_loss: bool,
+    /// Fade in when resuming playback
+    pub fade_in: bool,
+    /// Require pressing quit twice while playing
+    pub confirm_quit: bool,
+    /// Stop playback when quitting with `q` instead of playing on in the background
+    pub stop_on_quit: bool,
+    /// Restart the listening time shown in the UI when switching stations
+    pub reset_session_clock: bool,
+    /// Time between syncs with the player in milliseconds
+    pub polling_rate_ms: u64,
+    pub volume_scale: VolumeScale,
+    /// Remember the volume for each station instead of using the same one for all
+    pub per_station_volume: bool,
+    /// Volume change when pressing `v`/`V`
+    pub volume_step: f32,
+    /// Server-sent events feed with the now-playing info of all stations
+    pub metadata_url: Option<String>,
+    /// Separators of stream titles combining "Artist - Title" (used if there is no artist)
+    pub title_separators: Vec<String>,
+    /// Start with the single-line view
+    pub compact: bool,
+    /// Switch to the next station if the stream keeps buffering without a track (or the player keeps dying) for this long
+    pub dead_station_timeout_s: Option<u64>,
+    /// Show the current time
+    pub clock: bool,
+    /// Show a meter of the audio levels (adds a filter to mpv)
+    pub vu_meter: bool,
+    pub theme: Theme,
+    pub keymap: Keymap,
+    /// Command used to start mpv (e.g. a wrapper script)
+    pub player_command: String,
+    pub http_server: Option<HttpServer>,
+    /// Path of mpv's IPC socket (or named pipe on Windows)
+    pub ipc_socket: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            base_url: DEFAULT_STATION_BASE_URL.into(),
+            format: StreamFormat::default(),
+            stations: DEFAULT_STATIONS
+                .iter()
+                .map(|(name, title, description)| Station {
+                    name: name.to_string(),
+                    url: None,
+                    format: None,
+                    title: Some(title.to_string()),
+                    description: Some(description.to_string()),
+                    logo: None,
+                })
+                .collect(),
+            stations_url: None,
+            lastfm: None,
+            mpris: false,
+            pause_on_sleep: false,
+            pause_on_sink_removal: false,
+            pause_on_focus_loss: false,
+            fade_in: false,
+            confirm_quit: false,
+            stop_on_quit: false,
+            reset_session_clock: false,
+            polling_rate_ms: 500,
+            volume_scale: VolumeScale::default(),
+            per_station_volume: false,
+            volume_step: 5.0,
+            metadata_url: None,
+            title_separators: vec![" - ".into(), " – ".into(), " — ".into()],
+            compact: false,
+            dead_station_timeout_s: None,
+            clock: false,
+            vu_meter: false,
+            theme: Theme::default(),
+            keymap: Keymap::default(),
+            player_command: "mpv".into(),
+            http_server: None,
+            ipc_socket: None,
+        }
+    }
+}
+
+impl Config {
+    /// Where the user config is (if there is a home or config directory)
+    fn path(home: Option<PathBuf>, config_dir: Option<OsString>) -> Option<PathBuf> {
+        match home {
+            Some(home) => Some(home.join(USER_CONFIG_FILE_PATH)),
+            None => config_dir.map(|config_dir| PathBuf::from(config_dir).join(CONFIG_FILE_PATH)),
+        }
+    }
+
+    /// Load the user config, using the defaults if there is none
+    pub fn load() -> Result<Self> {
+        let path = Self::path(home_dir(), std::env::var_os("XDG_CONFIG_HOME"));
+        let Some(path) = path.filter(|path| path.exists()) else {
+            return Ok(Self::default());
+        };
+        let mut config: Self = toml::from_str(std::fs::read_to_string(path)?.as_str())?;
+        if config.stations_url.is_some() {
+            if let Some(stations) = cached_stations() {
+                config.stations = stations;
+            }
+        }
+        if config.stations.is_empty() {
+            return Err("Config must contain at least one station".into());
+        }
+        config
+            .keymap
+            .validate(crate::FIXED_KEYS)
+            .map_err(|e| format!("Invalid keymap: {}", e))?;
+        if config.polling_rate_ms < MIN_POLLING_RATE_MS {
+            return Err(format!("polling_rate_ms must be at least {}", MIN_POLLING_RATE_MS).into());
+        }
+        Ok(config)
+    }
+
+    /// Like `load`, but an invalid config is replaced with the defaults
+    /// The error is logged and returned as well, so that it can be shown
+    pub fn load_or_default() -> (Self, Option<String>) {
+        match Self::load() {
+            Ok(config) => (config, None),
+            Err(e) => {
+                // TOML errors quote the offending line below the position, which doesn't fit
+                // on a single line, so only the position and the reason are kept
+                let error = e.to_string();
+                let mut lines = error.lines().map(str::trim).filter(|line| !line.is_empty());
+                let error = match (lines.next(), lines.next_back()) {
+                    (Some(position), Some(reason)) => format!("{}: {}", position, reason),
+                    _ => error.clone(),
+                };
+                let message = format!("Invalid config, using the defaults: {}", error);
+                log::error!("{}", message);
+                (Self::default(), Some(message))
+            }
+        }
+    }
+
+    pub fn polling_rate(&self) -> Duration {
+        Duration::from_millis(self.polling_rate_ms)
+    }
+
+    pub fn station_url(&self, station: usize) -> String {
+        let station = &self.stations[station];
+        match &station.url {
+            Some(url) => url.clone(),
+            None => format!(
+                "{}{}.{}",
+                self.base_url,
+                station.name,
+                station.format.unwrap_or(self.format).extension()
+            ),
+        }
+    }
+
+    /// Find the station whose stream mpv reports as the given path (ignoring the case)
+    pub fn station_by_path(&self, path: &str) -> Option<usize> {
+        (0..self.stations.len())
+            .find(|&station| self.station_url(station).eq_ignore_ascii_case(path))
+    }
+}
+
+/// The stations cached on the last successful fetch (see `refresh_stations`)
+fn cached_stations() -> Option<Vec<Station>> {
+    let cache_path = home_dir()?.join(USER_STATIONS_CACHE_FILE_PATH);
+    let json = std::fs::read_to_string(cache_path).ok()?;
+    parse_stations(&json)
+        .inspect_err(|e| log::warn!("Could not use the cached stations: {}", e))
+        .ok()
+}
+
+/// Fetch the stations from the URL in the background, updating the cache used by `load`
+/// The stations are sent once the fetch succeeded
+pub fn refresh_stations(url: String) -> Receiver<Vec<Station>> {
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let fetched = fetch_stations(&url).and_then(|json| Ok((parse_stations(&json)?, json)));
+        match fetched {
+            Ok((stations, json)) => {
+                if let Some(home) = home_dir() {
+                    if let Err(e) =
+                        write_atomically(&home.join(USER_STATIONS_CACHE_FILE_PATH), &json)
+                    {
+                        log::warn!("Could not cache the stations: {}", e);
+                    }
+                }
+                sender.send(stations).ok();
+            }
+            Err(e) => log::warn!("Could not fetch the stations from {}: {}", url, e),
+        }
+    });
+    receiver
+}
+
+fn fetch_stations(url: &str) -> Result<String> {
+    let agent: ureq::Agent = ureq::Agent::config_builder()
+        .timeout_global(Some(STATIONS_FETCH_TIMEOUT))
+        .build()
+        .into();
+    Ok(agent.get(url).call()?.into_body().read_to_string()?)
+}
+
+/// Parse a JSON list of stations, making sure it can be used as the station list
+fn parse_stations(json: &str) -> Result<Vec<Station>> {
+    let stations: Vec<Station> = serde_json::from_str(json)?;
+    if stations.is_empty() {
+        return Err("The station list is empty".into());
+    }
+    let mut names = HashSet::new();
+    for station in &stations {
+        if station.name.is_empty() {
+            return Err("Stations must have a name".into());
+        }
+        if !names.insert(station.name.as_str()) {
+            return Err(format!("Station `{}` is listed twice", station.name).into());
+        }
+    }
+    Ok(stations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_path_without_home() {
+        assert_eq!(Config::path(None, None), None);
+        assert_eq!(
+            Config::path(None, Some("/config".into())),
+            Some(PathBuf::from("/config/nightride/config.toml"))
+        );
+        assert_eq!(
+            Config::path(Some("/home/user".into()), Some("/config".into())),
+            Some(PathBuf::from("/home/user/.config/nightride/config.toml"))
+        );
+    }
+
+    #[test]
+    fn station_by_path_compares_the_whole_url() {
+        let mut config = Config::default();
+        let url = config.station_url(1);
+        assert_eq!(config.station_by_path(&url.to_uppercase()), Some(1));
+        // Another format is another stream (e.g. after reloading the config)
+        assert_eq!(config.station_by_path(&url.replace(".ogg", ".m3u8")), None);
+
+        config.stations.truncate(1);
+        config.stations[0].url = Some("http://a.example/stream".into());
+        config.stations.push(Station {
+            name: "b".into(),
+            url: Some("http://b.example/stream".into()),
+            ..config.stations[0].clone()
+        });
+        assert_eq!(config.station_by_path("http://b.example/stream"), Some(1));
+    }
+}
@@ -0,0 +1,226 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{IpAddr, TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use crate::{
+    cli::{self, VolumeChange},
+    config, Result,
+};
+
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(100); // How often to check for connections
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_BODY_LENGTH: usize = 64; // Plenty for a volume
+
+/// Playback commands received via HTTP
+pub enum Command {
+    Play,
+    Pause,
+    Toggle,
+    Next,
+    Previous,
+    Volume(VolumeChange),
+}
+
+/// Serves a tiny control API (e.g. for scripts on another device) in the background:
+/// `GET /status` and `POST /play`, `/pause`, `/toggle`, `/next`, `/previous` and `/volume`
+/// (with the volume or change like `+5` as the body)
+/// Requests made by web pages (which send an `Origin`) are refused, since any page could
+/// otherwise control playback; without a token only requests to a local host are served,
+/// which also keeps pages from getting around that by rebinding their domain to this machine
+pub struct Server {
+    commands: Receiver<Command>,
+    /// The JSON served on `/status`, published by the app
+    status: Arc<Mutex<String>>,
+    is_running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Server {
+    pub fn start(settings: &config::HttpServer) -> Result<Self> {
+        if settings.token.is_none() && !is_local(&settings.address) {
+            return Err("A token is required to serve beyond this machine".into());
+        }
+        let token = settings.token.clone();
+        let listener = TcpListener::bind((settings.address.as_str(), settings.port))?;
+        // Polled, so the thread notices when the server is dropped
+        listener.set_nonblocking(true)?;
+        let (sender, receiver) = mpsc::channel();
+        let status = Arc::new(Mutex::new("{}".to_string()));
+        let is_running = Arc::new(AtomicBool::new(true));
+        let thread = std::thread::spawn({
+            let status = status.clone();
+            let is_running = is_running.clone();
+            move || {
+                while is_running.load(Ordering::Relaxed) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(e) = handle(stream, &sender, &status, token.as_deref()) {
+                                log::debug!("Could not handle HTTP request: {}", e);
+                            }
+                        }
+                        Err(_) => std::thread::sleep(ACCEPT_INTERVAL),
+                    }
+                }
+            }
+        });
+        Ok(Self {
+            commands: receiver,
+            status,
+            is_running,
+            thread: Some(thread),
+        })
+    }
+
+    /// Drain the commands received since the last call
+    pub fn commands(&self) -> impl Iterator<Item = Command> + '_ {
+        self.commands.try_iter()
+    }
+
+    /// Publish the state served on `/status`
+    pub fn update(&self, status: String) {
+        if let Ok(mut current) = self.status.lock() {
+            *current = status;
+        }
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.is_running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+/// Whether the host (optionally with a port) refers to this machine
+fn is_local(host: &str) -> bool {
+    let host = match host.rsplit_once(':') {
+        // Not part of an IPv6 address like `::1`
+        Some((name, port)) if port.parse::<u16>().is_ok() && !name.ends_with(':') => name,
+        _ => host,
+    };
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.eq_ignore_ascii_case("localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Answer a single request (the connection is closed afterwards)
+fn handle(
+    stream: TcpStream,
+    sender: &Sender<Command>,
+    status: &Mutex<String>,
+    token: Option<&str>,
+) -> Result<()> {
+    // Accepted connections might inherit the listener's non-blocking mode
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(&stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut content_length = 0;
+    let mut has_origin = false;
+    let mut host = String::new();
+    let mut authorization = String::new();
+    loop {
+        let mut header = String::new();
+        reader.read_line(&mut header)?;
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("origin") {
+                has_origin = true;
+            } else if name.eq_ignore_ascii_case("host") {
+                host = value.to_string();
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorization = value.to_string();
+            }
+        }
+    }
+    let mut body = vec![0; content_length.min(MAX_BODY_LENGTH)];
+    reader.read_exact(&mut body)?;
+    let body = String::from_utf8_lossy(&body);
+    let is_authorized = match token {
+        Some(token) => authorization.strip_prefix("Bearer ") == Some(token),
+        None => is_local(&host),
+    };
+    if has_origin || !is_authorized {
+        return respond(&stream, "403 Forbidden", "text/plain", "");
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let command = match (parts.next(), parts.next()) {
+        (Some("GET"), Some("/status")) => {
+            let status = status
+                .lock()
+                .map(|status| status.clone())
+                .unwrap_or_default();
+            return respond(&stream, "200 OK", "application/json", &status);
+        }
+        (Some("POST"), Some("/play")) => Command::Play,
+        (Some("POST"), Some("/pause")) => Command::Pause,
+        (Some("POST"), Some("/toggle")) => Command::Toggle,
+        (Some("POST"), Some("/next")) => Command::Next,
+        (Some("POST"), Some("/previous")) => Command::Previous,
+        (Some("POST"), Some("/volume")) => match cli::parse_volume_change(body.trim()) {
+            Ok(volume) => Command::Volume(volume),
+            Err(e) => return respond(&stream, "400 Bad Request", "text/plain", &e),
+        },
+        (
+            _,
+            Some("/status" | "/play" | "/pause" | "/toggle" | "/next" | "/previous" | "/volume"),
+        ) => return respond(&stream, "405 Method Not Allowed", "text/plain", ""),
+        _ => return respond(&stream, "404 Not Found", "text/plain", ""),
+    };
+    match sender.send(command) {
+        Ok(()) => respond(&stream, "204 No Content", "text/plain", ""),
+        // The app is quitting
+        Err(_) => respond(&stream, "503 Service Unavailable", "text/plain", ""),
+    }
+}
+
+fn respond(mut stream: &TcpStream, status: &str, content_type: &str, body: &str) -> Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_local_hosts() {
+        for host in [
+            "localhost",
+            "localhost:8080",
+            "127.0.0.1:8080",
+            "[::1]:8080",
+            "::1",
+        ] {
+            assert!(is_local(host), "{}", host);
+        }
+        for host in ["0.0.0.0", "192.168.1.2:8080", "evil.example:8080", ""] {
+            assert!(!is_local(host), "{}", host);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use serde::Deserialize;
+
+/// Actions whose keys can be changed
+#[derive(Clone, Copy)]
+enum Action {
+    Pause,
+    NextStation,
+    PreviousStation,
+    VolumeUp,
+    VolumeDown,
+    Search,
+    Quit,
+}
+
+impl Action {
+    const ALL: [Action; 7] = [
+        Action::Pause,
+        Action::NextStation,
+        Action::PreviousStation,
+        Action::VolumeUp,
+        Action::VolumeDown,
+        Action::Search,
+        Action::Quit,
+    ];
+
+    fn default_key(self) -> char {
+        match self {
+            Action::Pause => 'p',
+            Action::NextStation => 'n',
+            Action::PreviousStation => 'N',
+            Action::VolumeUp => 'V',
+            Action::VolumeDown => 'v',
+            Action::Search => 'y',
+            Action::Quit => 'q',
+        }
+    }
+}
+
+/// Custom keys for some of the actions (the others keep their default keys)
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Keymap {
+    pub pause: Option<char>,
+    pub next_station: Option<char>,
+    pub prev_station: Option<char>,
+    pub volume_up: Option<char>,
+    pub volume_down: Option<char>,
+    pub search: Option<char>,
+    pub quit: Option<char>,
+}
+
+impl Keymap {
+    fn key(&self, action: Action) -> char {
+        let key = match action {
+            Action::Pause => self.pause,
+            Action::NextStation => self.next_station,
+            Action::PreviousStation => self.prev_station,
+            Action::VolumeUp => self.volume_up,
+            Action::VolumeDown => self.volume_down,
+            Action::Search => self.search,
+            Action::Quit => self.quit,
+        };
+        key.unwrap_or(action.default_key())
+    }
+
+    /// The key the action with the given default key is mapped to
+    /// (keys of actions that can't be changed are returned as they are)
+    pub fn active_key(&self, default_key: char) -> char {
+        match Action::ALL
+            .iter()
+            .find(|action| action.default_key() == default_key)
+        {
+            Some(&action) => self.key(action),
+            None => default_key,
+        }
+    }
+
+    /// Make sure that every action can be reached, i.e. no key is used twice
+    /// and none of the keys of the actions that can't be changed is taken
+    pub fn validate(&self, fixed_keys: &str) -> Result<(), String> {
+        let mut keys = Vec::new();
+        for action in Action::ALL {
+            let key = self.key(action);
+            if keys.contains(&key) {
+                return Err(format!("`{}` is mapped to several actions", key));
+            }
+            if fixed_keys.contains(key) {
+                return Err(format!("`{}` is already used by another action", key));
+            }
+            keys.push(key);
+        }
+        Ok(())
+    }
+
+    /// Translate the pressed key to the default key of the action it is mapped to
+    /// Returns `None` for default keys of actions that were moved to another key
+    pub fn translate(&self, key: char) -> Option<char> {
+        if let Some(action) = Action::ALL.iter().find(|&&action| self.key(action) == key) {
+            return Some(action.default_key());
+        }
+        match Action::ALL.iter().any(|action| action.default_key() == key) {
+            true => None,
+            false => Some(key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_rejects_conflicts() {
+        let fixed_keys = crate::FIXED_KEYS;
+        assert!(Keymap::default().validate(fixed_keys).is_ok());
+        let swapped = Keymap {
+            pause: Some('n'),
+            next_station: Some('p'),
+            ..Keymap::default()
+        };
+        assert!(swapped.validate(fixed_keys).is_ok());
+        let duplicate = Keymap {
+            pause: Some('n'),
+            ..Keymap::default()
+        };
+        assert!(duplicate.validate(fixed_keys).is_err());
+        let fixed = Keymap {
+            pause: Some('m'),
+            ..Keymap::default()
+        };
+        assert!(fixed.validate(fixed_keys).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::Track;
+
+const API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+/// How long a track has to be listened to before it is scrobbled
+const SCROBBLE_THRESHOLD: Duration = Duration::from_secs(30);
+
+/// Last.fm API credentials
+/// The session key has to be obtained once via Last.fm's authentication flow
+#[derive(Clone, Debug, Deserialize)]
+pub struct Credentials {
+    pub api_key: String,
+    pub api_secret: String,
+    pub session_key: String,
+}
+
+/// The track currently being listened to
+struct Listen {
+    track: Track,
+    started_at: u64, // Unix timestamp
+    listened: Duration,
+    last_update: Instant,
+    is_scrobbled: bool,
+}
+
+pub struct Scrobbler {
+    credentials: Credentials,
+    listen: Option<Listen>,
+}
+
+impl Scrobbler {
+    pub fn new(credentials: Credentials) -> Self {
+        Self {
+            credentials,
+            listen: None,
+        }
+    }
+
+    /// Keep track of what is playing.
+    /// Sends a "now playing" update for new tracks and scrobbles them once they have been
+    /// listened to for long enough.
+    pub fn update(&mut self, track: Option<&Track>, is_paused: bool) {
+        let Some(track) = track else {
+            return;
+        };
+        let is_new_track = match &self.listen {
+            Some(listen) => !listen.track.is_same_song(track),
+            None => true,
+        };
+        if is_new_track {
+            self.listen = Some(Listen {
+                track: track.clone(),
+                started_at: unix_timestamp(),
+                listened: Duration::ZERO,
+                last_update: Instant::now(),
+                is_scrobbled: false,
+            });
+            if !is_paused {
+                self.submit("track.updateNowPlaying", track, None);
+            }
+            return;
+        }
+        let Some(listen) = self.listen.as_mut() else {
+            return;
+        };
+        // Only count the time actually spent listening
+        if !is_paused {
+            listen.listened += listen.last_update.elapsed();
+        }
+        listen.last_update = Instant::now();
+        if !listen.is_scrobbled && listen.listened >= SCROBBLE_THRESHOLD {
+            listen.is_scrobbled = true;
+            let (track, started_at) = (listen.track.clone(), listen.started_at);
+            self.submit("track.scrobble", &track, Some(started_at));
+        }
+    }
+
+    /// Call an API method in the background (failures are ignored)
+    fn submit(&self, method: &str, track: &Track, timestamp: Option<u64>) {
+        let mut params = BTreeMap::from([
+            ("method", method.to_string()),
+            ("artist", track.artist.clone()),
+            ("track", track.title.clone()),
+            ("api_key", self.credentials.api_key.clone()),
+            ("sk", self.credentials.session_key.clone()),
+        ]);
+        if !track.album.is_empty() {
+            params.insert("album", track.album.clone());
+        }
+        if let Some(timestamp) = timestamp {
+            params.insert("timestamp", timestamp.to_string());
+        }
+        params.insert("api_sig", sign(&params, &self.credentials.api_secret));
+        params.insert("format", "json".into());
+        std::thread::spawn(move || {
+            ureq::post(API_URL).send_form(params).ok();
+        });
+    }
+}
+
+/// Compute the API method signature (the parameters must be sorted by name)
+fn sign(params: &BTreeMap<&str, String>, secret: &str) -> String {
+    let mut signature: String = params
+        .iter()
+        .map(|(name, value)| format!("{}{}", name, value))
+        .collect();
+    signature.push_str(secret);
+    format!("{:x}", md5::compute(signature))
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,72 @@
+use home::home_dir;
+use log::{LevelFilter, Log, Metadata, Record};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::Mutex,
+};
+
+use crate::Result;
+
+const USER_LOG_FILE_PATH: &str = ".local/share/nightride/log.txt"; // relative to home dir
+const DATA_LOG_FILE_PATH: &str = "nightride/log.txt"; // relative to $XDG_DATA_HOME
+
+/// Appends log records to a file, since the TUI occupies the terminal
+struct FileLogger {
+    level: LevelFilter,
+    file: Mutex<File>,
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let mut file = self.file.lock().unwrap();
+        writeln!(
+            file,
+            "{} {:<5} [{}] {}",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            record.level(),
+            record.target(),
+            record.args()
+        )
+        .ok();
+    }
+
+    fn flush(&self) {
+        self.file.lock().unwrap().flush().ok();
+    }
+}
+
+/// Start logging to the log file (in `$XDG_DATA_HOME` if there is no home directory)
+/// The level is taken from `$RUST_LOG` (e.g. `debug`), otherwise only warnings and errors
+/// are logged unless `verbose` is set
+pub fn init(verbose: bool) -> Result<()> {
+    let level = match std::env::var("RUST_LOG").ok().and_then(|l| l.parse().ok()) {
+        Some(level) => level,
+        None if verbose => LevelFilter::Debug,
+        None => LevelFilter::Warn,
+    };
+    let path = match home_dir() {
+        Some(home) => home.join(USER_LOG_FILE_PATH),
+        None => std::env::var_os("XDG_DATA_HOME")
+            .map(|data_dir| PathBuf::from(data_dir).join(DATA_LOG_FILE_PATH))
+            .ok_or("Could not get home or data directory")?,
+    };
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    log::set_boxed_logger(Box::new(FileLogger {
+        level,
+        file: Mutex::new(file),
+    }))?;
+    log::set_max_level(level);
+    Ok(())
+}
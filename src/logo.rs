@@ -0,0 +1,249 @@
+use base64::Engine;
+use std::{
+    collections::HashMap,
+    io::{self, Cursor, Write},
+    sync::{Arc, Mutex},
+};
+use tui::layout::Rect;
+
+use crate::Result;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const CHUNK_LENGTH: usize = 4096; // Maximum payload of a single escape sequence
+const IMAGE_ID: u32 = 1; // Only one image is shown at a time
+const CELL_ASPECT_RATIO: u32 = 2; // Cells are about twice as high as they are wide
+const MAX_CONVERTED_SIZE: u32 = 512; // Other formats are scaled down to this while converting
+const MAX_IMAGES: usize = 32; // Album art changes with every track, so the cache is limited
+
+/// A PNG image, ready to be sent to the terminal
+pub struct Image {
+    width: u32,
+    height: u32,
+    /// Base64-encoded, as the graphics protocol expects it
+    data: String,
+}
+
+impl Image {
+    /// Other formats (e.g. JPEG album art) are converted, since the protocol only takes PNG
+    fn decode(data: &[u8]) -> Result<Self> {
+        if data.starts_with(PNG_SIGNATURE) {
+            return Self::from_png(data);
+        }
+        let image = image::load_from_memory(data)?;
+        let image = match image.width().max(image.height()) > MAX_CONVERTED_SIZE {
+            true => image.thumbnail(MAX_CONVERTED_SIZE, MAX_CONVERTED_SIZE),
+            false => image,
+        };
+        let mut png = Vec::new();
+        image.write_to(&mut Cursor::new(&mut png), image::ImageFormat::Png)?;
+        Self::from_png(&png)
+    }
+
+    fn from_png(png: &[u8]) -> Result<Self> {
+        // The size is the start of the IHDR chunk, which has to come first
+        if png.len() < 24 || !png.starts_with(PNG_SIGNATURE) || &png[12..16] != b"IHDR" {
+            return Err("Not a PNG image".into());
+        }
+        let dimension =
+            |offset: usize| u32::from_be_bytes(png[offset..offset + 4].try_into().unwrap());
+        let (width, height) = (dimension(16), dimension(20));
+        if width == 0 || height == 0 {
+            return Err("The image is empty".into());
+        }
+        Ok(Self {
+            width,
+            height,
+            data: base64::engine::general_purpose::STANDARD.encode(png),
+        })
+    }
+
+    /// The largest area within `area` (centered horizontally) that keeps the aspect ratio
+    fn fit(&self, area: Rect) -> Rect {
+        let (width, height) = (self.width as u64, self.height as u64);
+        let mut columns = area.width as u64;
+        let mut rows = (columns * height).div_ceil(width * CELL_ASPECT_RATIO as u64);
+        if rows > area.height as u64 {
+            rows = area.height as u64;
+            columns = (rows * width * CELL_ASPECT_RATIO as u64 / height).min(area.width as u64);
+        }
+        let (columns, rows) = (columns.max(1) as u16, rows.max(1) as u16);
+        Rect::new(
+            area.x + area.width.saturating_sub(columns) / 2,
+            area.y,
+            columns,
+            rows,
+        )
+    }
+}
+
+/// Station logos and album art (files or URLs), loaded in the background on first use
+#[derive(Default)]
+pub struct Logos {
+    /// By source, `None` while loading or if the image couldn't be loaded
+    images: Arc<Mutex<HashMap<String, Option<Arc<Image>>>>>,
+}
+
+impl Logos {
+    pub fn get(&self, source: &str) -> Option<Arc<Image>> {
+        let mut images = self.images.lock().unwrap();
+        if let Some(image) = images.get(source) {
+            return image.clone();
+        }
+        // Anything still needed is simply loaded again
+        if images.len() >= MAX_IMAGES {
+            images.clear();
+        }
+        images.insert(source.to_string(), None);
+        let images = self.images.clone();
+        let source = source.to_string();
+        std::thread::spawn(move || match load(&source) {
+            Ok(image) => {
+                images.lock().unwrap().insert(source, Some(Arc::new(image)));
+            }
+            Err(e) => log::warn!("Could not load the image {}: {}", source, e),
+        });
+        None
+    }
+}
+
+fn load(source: &str) -> Result<Image> {
+    let data = match source.starts_with("http://") || source.starts_with("https://") {
+        true => ureq::get(source).call()?.into_body().read_to_vec()?,
+        false => std::fs::read(source)?,
+    };
+    Image::decode(&data)
+}
+
+/// Shows an image on top of the text via the kitty graphics protocol
+/// (also supported by e.g. WezTerm and Ghostty)
+/// Images are requested while drawing, but only sent to the terminal by `flush`
+pub struct Panel {
+    is_supported: bool,
+    requested: Option<(Arc<Image>, Rect)>,
+    shown: Option<(Arc<Image>, Rect)>,
+}
+
+impl Default for Panel {
+    fn default() -> Self {
+        Self {
+            is_supported: is_supported(),
+            requested: None,
+            shown: None,
+        }
+    }
+}
+
+impl Panel {
+    pub fn is_supported(&self) -> bool {
+        self.is_supported
+    }
+
+    /// Show the image within the area (until the next frame is drawn)
+    pub fn show(&mut self, image: Arc<Image>, area: Rect) {
+        if self.is_supported && area.width > 0 && area.height > 0 {
+            let area = image.fit(area);
+            self.requested = Some((image, area));
+        }
+    }
+
+    pub fn hide(&mut self) {
+        self.requested = None;
+    }
+
+    /// Update the terminal if the image (or where it goes) changed
+    pub fn flush(&mut self) -> io::Result<()> {
+        let is_unchanged = match (&self.requested, &self.shown) {
+            (Some((requested, requested_area)), Some((shown, shown_area))) => {
+                Arc::ptr_eq(requested, shown) && requested_area == shown_area
+            }
+            (None, None) => true,
+            _ => false,
+        };
+        if is_unchanged {
+            return Ok(());
+        }
+        let mut stdout = io::stdout().lock();
+        if self.shown.take().is_some() {
+            clear(&mut stdout)?;
+        }
+        if let Some((image, area)) = &self.requested {
+            crossterm::queue!(stdout, crossterm::cursor::MoveTo(area.x, area.y))?;
+            // Sent in chunks, without moving the cursor or replying (which would show up as input)
+            let chunks: Vec<&[u8]> = image.data.as_bytes().chunks(CHUNK_LENGTH).collect();
+            for (i, chunk) in chunks.iter().enumerate() {
+                let has_more = (i + 1 < chunks.len()) as u8;
+                match i {
+                    0 => write!(
+                        stdout,
+                        "\x1b_Ga=T,f=100,i={},c={},r={},C=1,q=2,m={};",
+                        IMAGE_ID, area.width, area.height, has_more
+                    )?,
+                    _ => write!(stdout, "\x1b_Gm={};", has_more)?,
+                }
+                stdout.write_all(chunk)?;
+                write!(stdout, "\x1b\\")?;
+            }
+            self.shown = self.requested.clone();
+        }
+        stdout.flush()
+    }
+}
+
+/// Remove the image from the terminal (e.g. before leaving the alternate screen)
+pub fn clear(out: &mut impl Write) -> io::Result<()> {
+    write!(out, "\x1b_Ga=d,d=I,i={},q=2\x1b\\", IMAGE_ID)?;
+    out.flush()
+}
+
+/// Whether the terminal seems to support the kitty graphics protocol
+/// Terminal multiplexers would need the images to be passed through, so they are left out
+pub fn is_supported() -> bool {
+    let var = |name: &str| std::env::var(name).unwrap_or_default();
+    if std::env::var_os("TMUX").is_some() || var("TERM").starts_with("screen") {
+        return false;
+    }
+    std::env::var_os("KITTY_WINDOW_ID").is_some()
+        || var("TERM").contains("kitty")
+        || matches!(var("TERM_PROGRAM").as_str(), "WezTerm" | "ghostty")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut png = PNG_SIGNATURE.to_vec();
+        png.extend_from_slice(&13u32.to_be_bytes());
+        png.extend_from_slice(b"IHDR");
+        png.extend_from_slice(&width.to_be_bytes());
+        png.extend_from_slice(&height.to_be_bytes());
+        png
+    }
+
+    #[test]
+    fn from_png_reads_the_size() {
+        let image = Image::from_png(&png(300, 100)).unwrap();
+        assert_eq!((image.width, image.height), (300, 100));
+        assert!(Image::from_png(b"GIF89a").is_err());
+        assert!(Image::from_png(&png(0, 100)).is_err());
+    }
+
+    #[test]
+    fn decode_converts_other_formats() {
+        let mut jpeg = Vec::new();
+        image::DynamicImage::new_rgb8(1024, 256)
+            .write_to(&mut Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .unwrap();
+        let image = Image::decode(&jpeg).unwrap();
+        assert_eq!((image.width, image.height), (512, 128));
+        assert!(Image::decode(b"not an image").is_err());
+    }
+
+    #[test]
+    fn fit_keeps_the_aspect_ratio() {
+        let square = Image::from_png(&png(100, 100)).unwrap();
+        assert_eq!(square.fit(Rect::new(0, 0, 40, 5)), Rect::new(15, 0, 10, 5));
+        let wide = Image::from_png(&png(400, 100)).unwrap();
+        assert_eq!(wide.fit(Rect::new(0, 0, 8, 5)), Rect::new(0, 0, 8, 1));
+    }
+}
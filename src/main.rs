@@ -1,22 +1,56 @@
+mod cli;
+mod config;
+#[cfg(feature = "http")]
+mod http;
+mod keymap;
+mod lastfm;
+mod logger;
+#[cfg(feature = "logo")]
+mod logo;
+mod metadata;
+mod mpris;
+mod observer;
+mod platform;
+#[cfg(target_os = "linux")]
+mod sink;
+#[cfg(target_os = "linux")]
+mod sleep;
+mod theme;
+
+use clap::{CommandFactory, Parser};
+use config::Config;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        self, DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, MouseButton, MouseEvent, MouseEventKind,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use home::home_dir;
+use keymap::Keymap;
+use platform::Platform;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::{HashMap, VecDeque},
     error::Error,
+    ffi::OsString,
     fmt::Display,
-    io,
-    process::Command,
+    fs::{File, TryLockError},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    rc::Rc,
     time::{Duration, Instant},
 };
+use theme::Theme;
 use tui::{
     backend::{Backend, CrosstermBackend},
-    layout::{Alignment, Constraint, Direction, Layout},
-    text::{Spans, Text},
-    widgets::{Block, BorderType, Paragraph},
+    layout::{Alignment, Constraint, Direction, Layout, Margin, Rect},
+    style::{Color, Modifier, Style},
+    text::{Span, Spans, Text},
+    widgets::{Block, BorderType, Borders, Clear, Gauge, List, ListItem, ListState, Paragraph},
     Frame, Terminal,
 };
 
@@ -24,63 +58,256 @@ type Result<T> = std::result::Result<T, Box<dyn Error>>;
 
 // Constants
 const APP_TITLE: &str = "Nightride FM - The Home of Synthwave";
-const STATION_BASE_URL: &str = "http://stream.nightride.fm/";
-const STATIONS: [&str; 7] = [
-    "nightride",
-    "chillsynth",
-    "datawave",
-    "spacesynth",
-    "darksynth",
-    "horrorsynth",
-    "ebsm",
+const MAX_VOLUME: f32 = 150.0;
+const MAX_UNBOOSTED_VOLUME: f32 = 100.0; // Unless the boost is enabled (for the percent scale)
+const FINE_VOLUME_STEP: f32 = 1.0;
+const MAX_VOLUME_INPUT_LENGTH: usize = 3;
+const IPC_TIMEOUT: Duration = Duration::from_millis(500);
+const IPC_RETRIES: usize = 10;
+const IPC_RETRY_DELAY: Duration = Duration::from_millis(200);
+const IPC_STARTUP_TIMEOUT: Duration = Duration::from_secs(3);
+const FADE_DURATION: Duration = Duration::from_millis(300);
+const FADE_STEPS: u32 = 10;
+const OBSERVER_LATENCY: Duration = Duration::from_millis(100); // How often to check for changes
+const STATION_SWITCH_DELAY: Duration = Duration::from_millis(400);
+/// Keys that can't be changed in the keymap
+const FIXED_KEYS: &str = " ?hDilBtT+-0)=mRcofxsewdCrQSbg123456789";
+/// Single keys are shown as mapped in the keymap
+const KEYBINDINGS: [(&str, &str); 33] = [
+    ("p/Space", "Play/pause"),
+    ("t/T", "Stop (quit the player)/start playback"),
+    ("v/V", "Decrease/increase volume"),
+    ("-/+", "Decrease/increase volume by 1"),
+    ("0/)", "Set the volume to 0/100"),
+    ("=", "Enter the volume (digits, then Enter)"),
+    ("m", "Mute/unmute"),
+    (
+        "B",
+        "Toggle the volume boost beyond 100% (percent scale only)",
+    ),
+    (
+        "y/S/b/g",
+        "Search the current track on YouTube Music/Spotify/Bandcamp/the web",
+    ),
+    ("c", "Copy the current track to the clipboard"),
+    ("o", "Open the stream URL externally"),
+    ("f", "Add the current track to the favorites"),
+    ("n/N", "Next/previous station"),
+    ("1-9", "Select station"),
+    ("R", "Switch to a random station"),
+    ("Backspace", "Switch back to the last station"),
+    ("x", "Reconnect to the station (e.g. if playback stutters)"),
+    ("l", "Pick a station from the list (Up/Down, Enter)"),
+    ("s", "Set the sleep timer (off/15/30/60 minutes)"),
+    ("w", "Start/stop recording the stream"),
+    ("e", "Cycle the equalizer presets (flat/bass boost/vocal)"),
+    ("d", "Toggle desktop notifications on track change"),
+    ("C", "Toggle the compact single-line view"),
+    ("r", "Reload the config file"),
+    ("?", "Toggle this help"),
+    ("Up/Down", "Scroll this help (if it doesn't fit)"),
+    ("h", "Toggle the history of played tracks"),
+    ("D", "Toggle the recent errors (diagnostics)"),
+    ("i", "Toggle the raw metadata sent by the station"),
+    ("Delete", "Clear the recent errors (while shown)"),
+    (
+        "Esc",
+        "Close this help/the history/the diagnostics/the metadata",
+    ),
+    ("q/Esc", "Quit (playback continues in the background)"),
+    ("Q", "Quit and stop playback"),
+];
+const IPC_FAILURE_THRESHOLD: u32 = 3; // Consecutive failed updates until mpv is considered dead
+const MIN_RESTART_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+const ERROR_TIMEOUT: Duration = Duration::from_secs(5);
+const TRACK_HIGHLIGHT_DURATION: Duration = Duration::from_secs(3);
+const METADATA_TIMEOUT: Duration = Duration::from_secs(10); // Until a stream is considered to have none
+const TOAST_TIMEOUT: Duration = Duration::from_millis(1500);
+const MIN_ROOMY_HEIGHT: u16 = 16; // Smaller screens get the compact layout
+const MIN_ROOMY_WIDTH: u16 = 40;
+const MIN_BANNER_HEIGHT: u16 = 24; // Smaller screens don't show the banner
+const BANNER: [&str; 5] = [
+    "▄▄██████▄▄",
+    "▄████████████▄",
+    "▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀▀",
+    "════════════════════",
+    "╱   ╱   ╱   ╲   ╲   ╲",
+];
+const MIN_ALBUM_CHARS: usize = 5; // With less room, the album is left out instead of truncated
+const MIN_ALBUM_WIDTH: u16 = 60; // Narrower screens leave out the album
+const QUIT_CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(3);
+const SLEEP_TIMER_OPTIONS: [Option<Duration>; 4] = [
+    None,
+    Some(Duration::from_secs(15 * 60)),
+    Some(Duration::from_secs(30 * 60)),
+    Some(Duration::from_secs(60 * 60)),
+];
+/// Names and mpv audio filters of the equalizer presets
+const EQ_PRESETS: [(&str, &str); 3] = [
+    ("flat", ""),
+    (
+        "bass boost",
+        "lavfi=[equalizer=f=80:width_type=o:width=2:g=6]",
+    ),
+    ("vocal", "lavfi=[equalizer=f=2500:width_type=o:width=2:g=4]"),
 ];
-const INPUT_IPC_SERVER_FILE_PATH: &str = "/tmp/nightride.sock";
-const POLLING_RATE: Duration = Duration::from_secs(1);
-const YT_MUSIC_SEARCH_URL: &str = "https://music.youtube.com/search?q=";
+/// Measures the audio levels (read from the `af-metadata/vu` property)
+const VU_METER_FILTER: &str = "@vu:lavfi=[astats=metadata=1:reset=1]";
+const VU_METER_LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+const VU_METER_LENGTH: usize = 12; // Number of updates shown
+const VU_METER_FLOOR_DB: f32 = -60.0; // Quieter counts as silence
+/// Properties read on every update (in the order `App::update` expects them)
+const SYNCED_PROPERTIES: [&str; 7] = [
+    "pause",
+    "volume",
+    "paused-for-cache",
+    "core-idle",
+    "metadata",
+    "path",
+    "af-metadata/vu",
+];
+const MAX_HISTORY_LENGTH: usize = 50;
+const MAX_DIAGNOSTICS_LENGTH: usize = 20;
+const MARQUEE_SEPARATOR: &str = "   ~   ";
+const SPINNER_FRAMES: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+const LOCK_FILE_NAME: &str = "nightride.lock"; // in the runtime dir
 const USER_SERIALIZED_APP_FILE_PATH: &str = ".local/share/nightride/app.json"; // relative to home dir
+const DATA_SERIALIZED_APP_FILE_PATH: &str = "nightride/app.json"; // relative to $XDG_DATA_HOME
+const USER_RECORDINGS_DIR_PATH: &str = "nightride-recordings"; // relative to home dir
+const USER_FAVORITES_FILE_PATH: &str = ".local/share/nightride/favorites.json"; // relative to home dir
+
+/// Start the player at the given volume
+fn mpv_start(player_command: &str, station_url: &str, volume: f32) -> Result<()> {
+    log::info!("Starting the player for {}", station_url);
+    platform::Native::spawn_detached(
+        player_command,
+        &[
+            station_url,
+            format!("--volume={}", volume).as_str(),
+            format!("--input-ipc-server={}", platform::ipc_endpoint()).as_str(),
+        ],
+    )
+}
 
-/// Start the player
-fn mpv_start(station: usize) -> Result<()> {
-    let station_url = format!("{}{}.ogg", STATION_BASE_URL, STATIONS[station]);
-    // Use nohup to avoid the process being killed when the terminal is closed
-    Command::new("nohup")
-        .args([
-            "mpv",
-            station_url.as_str(),
-            format!("--input-ipc-server={}", INPUT_IPC_SERVER_FILE_PATH).as_str(),
-            ">/dev/null", // Do not create nohup.out
-            "2>&1",       // Redirect stderr to stdout
-            "&",          // Run in background
-        ])
-        .stdout(std::process::Stdio::null())
-        .stderr(std::process::Stdio::null())
+/// Start dumping the stream to a file using a separate mpv instance,
+/// so that recording and playback don't affect each other
+fn start_recording(player_command: &str, station_url: &str, station_name: &str) -> Result<Child> {
+    let dir = home_dir()
+        .ok_or("Could not get home directory")?
+        .join(USER_RECORDINGS_DIR_PATH);
+    std::fs::create_dir_all(&dir)?;
+    let extension = station_url
+        .rsplit('/')
+        .next()
+        .and_then(|filename| filename.rsplit_once('.'))
+        .map_or("ogg", |(_, extension)| extension);
+    let path = dir.join(format!(
+        "{}-{}.{}",
+        station_name,
+        chrono::Local::now().format("%Y-%m-%d_%H-%M-%S"),
+        extension
+    ));
+    let process = Command::new(player_command)
+        .arg(station_url)
+        .arg(format!("--stream-dump={}", path.display()))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
         .spawn()?;
-    Ok(())
+    Ok(process)
 }
 
 /// Stop the player
-/// This will query the socket for the PID of the running process and kill it
-fn mpv_stop() -> Result<()> {
-    if let Ok(pid) = mpv_get_property::<u32>("pid") {
-        // Ignore errors (MPV might not have been running)
-        Command::new("kill").arg(pid.to_string()).output()?;
+/// This will query the socket for the PID of the running process, kill it and wait
+/// (for a bounded time) until it no longer answers, since the dying process would
+/// otherwise still receive the commands meant for its successor
+/// Returns the PID of the stopped player
+fn mpv_stop() -> Result<Option<u32>> {
+    // Ignore errors (MPV might not have been running)
+    let Ok(pid) = mpv_get_property::<u32>("pid") else {
+        return Ok(None);
+    };
+    platform::Native::kill(pid)?;
+    let deadline = Instant::now() + IPC_STARTUP_TIMEOUT;
+    while mpv_get_property::<u32>("pid").is_ok_and(|running_pid| running_pid == pid) {
+        if Instant::now() >= deadline {
+            log::warn!("The old player (PID {}) is still answering", pid);
+            break;
+        }
+        std::thread::sleep(IPC_RETRY_DELAY);
     }
-    Ok(())
+    Ok(Some(pid))
 }
 
-/// Ensure that the player is running and playing the station
-fn ensure_playing_station(station: usize) -> Result<()> {
-    let is_running_station = mpv_get_property::<String>("filename")
-        .unwrap_or("".into())
-        .split(".")
-        .nth(0)
-        .unwrap_or("")
-        == STATIONS[station];
+/// Ensure that the player is running and playing the station at the given volume
+/// Switching stations fades out the old one and fades in the new one
+/// Returns `true` if the player had to be (re)started
+fn ensure_playing_station(config: &Config, station: usize, volume: f32) -> Result<bool> {
+    let is_running_station = mpv_get_property::<String>("path")
+        .ok()
+        .and_then(|path| config.station_by_path(&path))
+        == Some(station);
     if !is_running_station {
-        mpv_stop()?;
-        mpv_start(station)?;
+        log::info!("Switching to station {}", config.stations[station].name);
+        if let Ok(current_volume) = mpv_get_property::<f32>("volume") {
+            fade_volume(current_volume, 0.0);
+        }
+        let stopped_pid = mpv_stop()?;
+        mpv_start(
+            &config.player_command,
+            config.station_url(station).as_str(),
+            0.0,
+        )?;
+        if !wait_for_player(stopped_pid) {
+            log::error!("The player did not accept IPC connections in time");
+            return Err("The player did not start in time".into());
+        }
+        // The volume is restored either way, a slow stream just starts without fading in
+        if !wait_for_playback() {
+            log::warn!("The stream did not start playing in time");
+        }
+        fade_volume(0.0, volume);
     }
-    Ok(())
+    Ok(!is_running_station)
+}
+
+/// Gradually change the volume of the running player
+fn fade_volume(from: f32, to: f32) {
+    for step in 1..=FADE_STEPS {
+        let volume = from + (to - from) * step as f32 / FADE_STEPS as f32;
+        if mpv_set_property("volume", volume).is_err() {
+            break;
+        }
+        std::thread::sleep(FADE_DURATION / FADE_STEPS);
+    }
+}
+
+/// Wait (for a bounded time) until the freshly started player answers IPC commands
+/// Answers from the stopped player (identified by its PID) don't count
+/// Returns `false` if it didn't come up in time
+fn wait_for_player(stopped_pid: Option<u32>) -> bool {
+    let deadline = Instant::now() + IPC_STARTUP_TIMEOUT;
+    while mpv_get_property::<u32>("pid").map_or(true, |pid| Some(pid) == stopped_pid) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(IPC_RETRY_DELAY);
+    }
+    true
+}
+
+/// Wait (for a bounded time) until the player is done connecting and plays the stream
+/// Returns `false` if it didn't start in time
+fn wait_for_playback() -> bool {
+    let deadline = Instant::now() + IPC_STARTUP_TIMEOUT;
+    while mpv_get_property::<bool>("core-idle").unwrap_or(true) {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(IPC_RETRY_DELAY);
+    }
+    true
 }
 
 #[derive(Deserialize)]
@@ -89,30 +316,53 @@ struct MpvProperty<T> {
     error: String,
 }
 
+/// Send a command to mpv via its IPC socket and parse the reply
+fn mpv_command<T: for<'a> serde::de::Deserialize<'a>>(
+    command: serde_json::Value,
+) -> Result<MpvProperty<T>> {
+    let result = send_mpv_command(&command);
+    if let Err(e) = &result {
+        // Expected while the player is (re)starting, so not worth a warning
+        log::debug!("IPC command {} failed: {}", command, e);
+    }
+    result
+}
+
+fn send_mpv_command<T: for<'a> serde::de::Deserialize<'a>>(
+    command: &serde_json::Value,
+) -> Result<MpvProperty<T>> {
+    let mut stream = platform::Native::connect(Some(IPC_TIMEOUT))?;
+    let mut request = serde_json::to_string(&serde_json::json!({ "command": command }))?;
+    request.push('\n');
+    stream.write_all(request.as_bytes())?;
+    read_reply(BufReader::new(stream))
+}
+
+/// Read the reply to a command
+/// Events (which lack the `error` field) can arrive before it
+fn read_reply<T: for<'a> serde::de::Deserialize<'a>>(
+    reader: impl BufRead,
+) -> Result<MpvProperty<T>> {
+    for line in reader.lines() {
+        let message: serde_json::Value = serde_json::from_str(&line?)?;
+        if message.get("error").is_some() {
+            return Ok(serde_json::from_value(message)?);
+        }
+    }
+    Err("The player closed the connection".into())
+}
+
 fn mpv_get_property<T: for<'a> serde::de::Deserialize<'a>>(property: &str) -> Result<T> {
-    let shell_cmd = format!(
-        "echo '{{\"command\":[\"get_property\",\"{}\"]}}' | socat - {}",
-        property, INPUT_IPC_SERVER_FILE_PATH
-    );
-    let shell_output = Command::new("sh").arg("-c").arg(shell_cmd).output()?;
-    let result_json = String::from_utf8(shell_output.stdout)?;
-    let result: MpvProperty<T> = serde_json::from_str(result_json.as_str())?;
-    if result.error != "success" || result.data.is_none() {
-        Err(result.error.into())
-    } else {
-        Ok(result.data.unwrap())
+    let result: MpvProperty<T> = mpv_command(serde_json::json!(["get_property", property]))?;
+    match result.data {
+        Some(data) if result.error == "success" => Ok(data),
+        _ => Err(result.error.into()),
     }
 }
 
 fn mpv_set_property<T: serde::Serialize>(property: &str, value: T) -> Result<()> {
-    let value_json = serde_json::to_string(&value)?;
-    let shell_cmd = format!(
-        "echo '{{\"command\":[\"set_property\",\"{}\",{}]}}' | socat - {}",
-        property, value_json, INPUT_IPC_SERVER_FILE_PATH
-    );
-    let shell_output = Command::new("sh").arg("-c").arg(shell_cmd).output()?;
-    let result_json = String::from_utf8(shell_output.stdout)?;
-    let result: MpvProperty<()> = serde_json::from_str(result_json.as_str())?;
+    let result: MpvProperty<()> =
+        mpv_command(serde_json::json!(["set_property", property, value]))?;
     if result.error == "success" {
         Ok(())
     } else {
@@ -120,24 +370,158 @@ fn mpv_set_property<T: serde::Serialize>(property: &str, value: T) -> Result<()>
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Reply to a command sent with a `request_id`
+#[derive(Deserialize)]
+struct MpvReply {
+    request_id: Option<usize>,
+    data: Option<serde_json::Value>,
+    error: String,
+}
+
+/// Get several properties in a single exchange (so they are consistent with each other)
+/// Properties that aren't available are `None`
+fn mpv_get_properties<const N: usize>(
+    properties: [&str; N],
+) -> Result<[Option<serde_json::Value>; N]> {
+    let mut stream = platform::Native::connect(Some(IPC_TIMEOUT))?;
+    let mut request = String::new();
+    for (id, property) in properties.iter().enumerate() {
+        let command =
+            serde_json::json!({ "command": ["get_property", property], "request_id": id });
+        request.push_str(&format!("{}\n", command));
+    }
+    stream.write_all(request.as_bytes())?;
+    let mut values = [(); N].map(|_| None);
+    let mut pending = N;
+    let mut lines = BufReader::new(stream).lines();
+    while pending > 0 {
+        let line = lines.next().ok_or("The player closed the connection")??;
+        // Skip events, which are sent to every client
+        let Ok(reply) = serde_json::from_str::<MpvReply>(&line) else {
+            continue;
+        };
+        let Some(id) = reply.request_id.filter(|&id| id < N) else {
+            continue;
+        };
+        pending -= 1;
+        if reply.error == "success" {
+            values[id] = reply.data;
+        }
+    }
+    Ok(values)
+}
+
+/// Convert a value returned by `mpv_get_properties` (`None` if it has another type)
+fn property_value<T: for<'a> serde::de::Deserialize<'a>>(
+    value: Option<serde_json::Value>,
+) -> Option<T> {
+    value.and_then(|value| serde_json::from_value(value).ok())
+}
+
+/// Set a property, retrying for a while in case mpv is still starting up
+fn mpv_set_property_with_retry<T: serde::Serialize>(property: &str, value: T) -> Result<()> {
+    let mut result = mpv_set_property(property, &value);
+    for _ in 1..IPC_RETRIES {
+        if result.is_ok() {
+            break;
+        }
+        std::thread::sleep(IPC_RETRY_DELAY);
+        result = mpv_set_property(property, &value);
+    }
+    result
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 struct Track {
     title: String,
+    /// Streams don't always provide the artist and album
+    #[serde(default)]
     artist: String,
+    #[serde(default)]
     album: String,
 }
 
+/// Where a track can be searched, `{query}` in the URL is replaced with the search terms
+#[derive(Clone, Copy)]
+enum SearchService {
+    YouTubeMusic,
+    Spotify,
+    Bandcamp,
+    Web,
+}
+
+impl SearchService {
+    fn url_template(self) -> &'static str {
+        match self {
+            SearchService::YouTubeMusic => "https://music.youtube.com/search?q={query}",
+            SearchService::Spotify => "https://open.spotify.com/search/{query}",
+            SearchService::Bandcamp => "https://bandcamp.com/search?q={query}",
+            SearchService::Web => "https://duckduckgo.com/?q={query}",
+        }
+    }
+}
+
 impl Display for Track {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} by {} ({})", self.title, self.artist, self.album)
+        write!(f, "{} by {}", self.title, self.artist)?;
+        if !self.album.is_empty() {
+            write!(f, " ({})", self.album)?;
+        }
+        Ok(())
     }
 }
 
 impl Track {
-    fn search_yt_music(&self) {
-        let search_url =
-            format!("{}{} {}", YT_MUSIC_SEARCH_URL, self.title, self.artist).replace(" ", "+");
-        Command::new("xdg-open").arg(search_url).spawn().ok();
+    fn search(&self, service: SearchService) {
+        platform::open_url(self.search_url(service).as_str());
+    }
+
+    /// The URL to search the track with the service, with the query percent-encoded
+    fn search_url(&self, service: SearchService) -> String {
+        let query = format!("{} {}", self.title, self.artist);
+        service
+            .url_template()
+            .replace("{query}", &urlencoding::encode(&query))
+    }
+
+    /// Fill in the `{title}`, `{artist}` and `{album}` placeholders of the template
+    fn format(&self, template: &str) -> String {
+        template
+            .replace("{title}", &self.title)
+            .replace("{artist}", &self.artist)
+            .replace("{album}", &self.album)
+    }
+
+    fn is_same_song(&self, other: &Track) -> bool {
+        self.title == other.title && self.artist == other.artist
+    }
+
+    /// Append the track to the favorites file
+    /// Returns `false` if it already is a favorite
+    fn add_to_favorites(&self) -> Result<bool> {
+        let path = home_dir()
+            .ok_or("Could not get home directory")?
+            .join(USER_FAVORITES_FILE_PATH);
+        let mut favorites: Vec<Track> = match path.exists() {
+            true => serde_json::from_str(std::fs::read_to_string(&path)?.as_str())?,
+            false => vec![],
+        };
+        if favorites.iter().any(|favorite| favorite.is_same_song(self)) {
+            return Ok(false);
+        }
+        favorites.push(self.clone());
+        write_atomically(&path, &serde_json::to_string_pretty(&favorites)?)?;
+        Ok(true)
+    }
+
+    /// Show a desktop notification for the track
+    fn notify(&self) {
+        notify_rust::Notification::new()
+            .appname("Nightride FM")
+            .summary(&self.title)
+            .body(&self.artist)
+            .show()
+            .ok();
     }
 }
 
@@ -148,195 +532,2339 @@ struct App {
     current_track: Option<Track>,
     volume: f32,
     station: usize,
+    /// Previously played tracks (most recent first)
+    #[serde(default)]
+    history: VecDeque<Track>,
+    /// Show a desktop notification when the track changes
+    #[serde(default)]
+    notifications: bool,
+    /// Index into `EQ_PRESETS`
+    #[serde(default)]
+    eq_preset: usize,
+    /// The station selected before the current one
+    #[serde(default)]
+    last_station: Option<usize>,
+    /// Volume last set on each station (by name)
+    #[serde(default)]
+    station_volumes: HashMap<String, f32>,
+    /// Whether the player was stopped on purpose (so it isn't restarted)
+    #[serde(skip)]
+    is_stopped: bool,
+    /// Whether playback is stalled (e.g. while connecting or on network hiccups)
+    #[serde(skip)]
+    is_buffering: bool,
+    /// Volume to restore when unmuting (`None` if not muted)
+    #[serde(skip)]
+    pre_mute_volume: Option<f32>,
+    /// Allow going beyond 100% on the percent volume scale
+    #[serde(skip)]
+    volume_boost: bool,
+    /// Show everything on a single line
+    #[serde(skip)]
+    compact: bool,
+    /// Whether the terminal should get colors (see `supports_color`)
+    #[serde(skip)]
+    use_color: bool,
+    #[serde(skip)]
+    show_help: bool,
+    /// How many lines of the help are scrolled out of view
+    #[serde(skip)]
+    help_scroll: u16,
+    #[serde(skip)]
+    show_history: bool,
+    #[serde(skip)]
+    show_diagnostics: bool,
+    /// The raw stream metadata (pretty-printed) while it is shown
+    #[serde(skip)]
+    metadata_dump: Option<String>,
+    /// The digits typed so far while entering the volume
+    #[serde(skip)]
+    volume_input: Option<String>,
+    /// The highlighted station while the station picker is open
+    #[serde(skip)]
+    station_picker: Option<usize>,
+    /// Scroll position of the track line (if it is too long to fit)
+    #[serde(skip)]
+    marquee_offset: usize,
+    /// Advanced on every update to animate the spinner while connecting
+    #[serde(skip)]
+    spinner_frame: usize,
+    /// Since when the player is reachable but has no usable track info
+    #[serde(skip)]
+    metadata_missing_since: Option<Instant>,
+    /// Since when the stream is stalled without a track (or the player keeps dying), and on which station
+    #[serde(skip)]
+    stalled_since: Option<(Instant, usize)>,
+    /// When a new track started (to highlight it)
+    #[serde(skip)]
+    track_changed_at: Option<Instant>,
+    /// Quitting has to be confirmed until then
+    #[serde(skip)]
+    confirm_quit_until: Option<Instant>,
+    /// The last error and when to stop showing it
+    #[serde(skip)]
+    error: Option<(String, Instant)>,
+    /// Recent errors and when they happened (most recent first)
+    #[serde(skip)]
+    diagnostics: VecDeque<(chrono::DateTime<chrono::Local>, String)>,
+    /// Feedback on the last action and when to stop showing it
+    #[serde(skip)]
+    toast: Option<(String, Instant)>,
+    /// Index into `SLEEP_TIMER_OPTIONS`
+    #[serde(skip)]
+    sleep_timer_option: usize,
+    /// Playback is paused at this time
+    #[serde(skip)]
+    sleep_deadline: Option<Instant>,
+    /// Number of consecutive updates that failed to reach mpv
+    #[serde(skip)]
+    ipc_failures: u32,
+    /// When the player was last reached
+    #[serde(skip)]
+    last_sync: Option<Instant>,
+    #[serde(skip)]
+    next_restart: Option<Instant>,
+    #[serde(skip)]
+    restart_backoff: Duration,
+    #[serde(skip)]
+    config: Config,
+    #[serde(skip)]
+    scrobbler: Option<lastfm::Scrobbler>,
+    #[serde(skip)]
+    metadata_feed: Option<metadata::Feed>,
+    /// The process recording the stream
+    #[serde(skip)]
+    recording: Option<Child>,
+    /// Kept around since the clipboard content is lost when it is dropped (on X11)
+    #[serde(skip)]
+    clipboard: Option<arboard::Clipboard>,
+    /// The selected station starts playing at this time
+    #[serde(skip)]
+    station_switch_deadline: Option<Instant>,
+    /// Time spent listening (unpaused) in this session
+    #[serde(skip)]
+    listening_time: Duration,
+    /// When the listening time was last updated and which station was playing
+    #[serde(skip)]
+    last_update: Option<(Instant, usize)>,
+    /// Restart the player on the next occasion (once the UI shows that it is reconnecting)
+    #[serde(skip)]
+    reconnect_pending: bool,
+    /// Recent peak levels (0-1, most recent last) for the VU meter, empty if unavailable
+    #[serde(skip)]
+    levels: VecDeque<f32>,
+    #[cfg(feature = "logo")]
+    #[serde(skip)]
+    logos: logo::Logos,
+    /// Where the album art or station logo is shown instead of the banner
+    #[cfg(feature = "logo")]
+    #[serde(skip)]
+    logo_panel: logo::Panel,
 }
 
 impl Default for App {
     fn default() -> Self {
         Self {
-            is_paused: true,
+            is_paused: false,
             current_track: None,
             volume: 100.0,
             station: 0,
+            history: VecDeque::new(),
+            notifications: false,
+            eq_preset: 0,
+            last_station: None,
+            station_volumes: HashMap::new(),
+            is_stopped: false,
+            is_buffering: false,
+            pre_mute_volume: None,
+            volume_boost: false,
+            compact: false,
+            use_color: true,
+            show_help: false,
+            help_scroll: 0,
+            show_history: false,
+            show_diagnostics: false,
+            metadata_dump: None,
+            volume_input: None,
+            station_picker: None,
+            marquee_offset: 0,
+            spinner_frame: 0,
+            metadata_missing_since: None,
+            stalled_since: None,
+            track_changed_at: None,
+            confirm_quit_until: None,
+            error: None,
+            diagnostics: VecDeque::new(),
+            toast: None,
+            sleep_timer_option: 0,
+            sleep_deadline: None,
+            ipc_failures: 0,
+            last_sync: None,
+            next_restart: None,
+            restart_backoff: MIN_RESTART_BACKOFF,
+            config: Config::default(),
+            scrobbler: None,
+            metadata_feed: None,
+            recording: None,
+            clipboard: None,
+            station_switch_deadline: None,
+            listening_time: Duration::ZERO,
+            last_update: None,
+            reconnect_pending: false,
+            levels: VecDeque::new(),
+            #[cfg(feature = "logo")]
+            logos: logo::Logos::default(),
+            #[cfg(feature = "logo")]
+            logo_panel: logo::Panel::default(),
         }
     }
 }
 
 impl App {
     fn update(&mut self) {
-        if let Ok(is_paused) = mpv_get_property("pause") {
-            self.is_paused = is_paused;
+        self.update_listening_time();
+        let [pause, volume, paused_for_cache, core_idle, metadata, path, levels] =
+            mpv_get_properties(SYNCED_PROPERTIES).unwrap_or_default();
+        let is_reachable = pause.is_some();
+        if self.is_stopped {
+            // Until something (e.g. switching stations) starts a player again
+            if !is_reachable {
+                self.is_buffering = false;
+                self.metadata_missing_since = None;
+                self.levels.clear();
+                return;
+            }
+            self.is_stopped = false;
         }
-        if let Ok(volume) = mpv_get_property("volume") {
+        match property_value(pause) {
+            Some(is_paused) => {
+                self.is_paused = is_paused;
+                self.last_sync = Some(Instant::now());
+                self.ipc_failures = 0;
+                self.next_restart = None;
+                self.restart_backoff = MIN_RESTART_BACKOFF;
+            }
+            None => {
+                self.ipc_failures += 1;
+                if self.is_reconnecting() {
+                    self.restart_player();
+                }
+            }
+        }
+        if let Some(volume) = property_value(volume) {
             self.volume = volume;
         }
-        self.current_track = get_track_info().ok();
-        if let Some(station) = STATIONS
-            .iter()
-            .position(|&s| s == mpv_get_property::<String>("filename").unwrap_or_default())
+        // mpv is idle while playing if it's waiting for data
+        let is_waiting = property_value(paused_for_cache).unwrap_or(false)
+            || property_value(core_idle).unwrap_or(false);
+        self.is_buffering = !self.is_paused && is_waiting;
+        self.update_levels(property_value(levels));
+        // Prefer the feed since it is more reliable than the stream metadata
+        let track = self
+            .metadata_feed
+            .as_ref()
+            .and_then(|feed| feed.track(&self.config.stations[self.station].name))
+            .or_else(|| {
+                property_value(metadata)
+                    .map(|metadata| parse_track(metadata, &self.config.title_separators))
+            });
+        self.metadata_missing_since = match (&track, is_reachable) {
+            (None, true) => self.metadata_missing_since.or(Some(Instant::now())),
+            _ => None,
+        };
+        if let (Some(previous), Some(track)) = (&self.current_track, &track) {
+            if !previous.is_same_song(track) {
+                self.track_changed_at = Some(Instant::now());
+                if self.notifications {
+                    track.notify();
+                }
+            }
+        }
+        if let Some(track) = &track {
+            if !self
+                .history
+                .front()
+                .is_some_and(|last| last.is_same_song(track))
+            {
+                self.history.push_front(track.clone());
+                self.history.truncate(MAX_HISTORY_LENGTH);
+            }
+        }
+        if let Some(scrobbler) = &mut self.scrobbler {
+            scrobbler.update(track.as_ref(), self.is_paused);
+        }
+        self.current_track = track;
+        // The player is still on the previous station while switching
+        if self.station_switch_deadline.is_some() {
+            self.stalled_since = None;
+            return;
+        }
+        self.skip_dead_station(is_reachable);
+        if let Some(station) =
+            property_value::<String>(path).and_then(|path| self.config.station_by_path(&path))
         {
             self.station = station;
         }
     }
 
-    fn load() -> Self {
-        let app = match serde_json::from_str(
-            std::fs::read_to_string(
-                home_dir()
-                    .unwrap_or_default()
-                    .join(USER_SERIALIZED_APP_FILE_PATH),
-            )
-            .unwrap_or("".into())
-            .as_str(),
-        ) {
-            Ok(app) => app,
-            Err(_) => Self::default(),
+    /// Move on to the next station if the current one seems to be down (if enabled)
+    fn skip_dead_station(&mut self, is_reachable: bool) {
+        let Some(timeout) = self.config.dead_station_timeout_s.map(Duration::from_secs) else {
+            return;
         };
-        ensure_playing_station(app.station).ok();
-        app
+        // The player also quits if the stream can't be loaded at all (e.g. if it is gone),
+        // so a player that keeps dying after restarting it counts as well
+        let keeps_dying = self.is_reconnecting() && self.next_restart.is_some();
+        let is_buffering = is_reachable && self.is_buffering && self.current_track.is_none();
+        self.stalled_since = match is_buffering || keeps_dying {
+            true => self
+                .stalled_since
+                .filter(|&(_, station)| station == self.station)
+                .or(Some((Instant::now(), self.station))),
+            false => None,
+        };
+        if self
+            .stalled_since
+            .is_none_or(|(since, _)| since.elapsed() < timeout)
+        {
+            return;
+        }
+        self.stalled_since = None;
+        // The next station gets a fresh start
+        self.next_restart = None;
+        self.restart_backoff = MIN_RESTART_BACKOFF;
+        let station = &self.config.stations[self.station];
+        log::warn!("Station {} seems to be down, skipping it", station.name);
+        let message = format!(
+            "{} seems to be down, skipping to the next station",
+            station.title.as_ref().unwrap_or(&station.name)
+        );
+        let next_station = (self.station + 1) % self.config.stations.len();
+        match self.play_station(next_station) {
+            Ok(()) => self.show_toast(&message),
+            Err(e) => self.show_error(e),
+        }
     }
 
-    fn store(&self) -> Result<()> {
-        // Make path if it doesn't exist
-        let path = home_dir()
-            .ok_or("Could not get home directory")?
-            .join(USER_SERIALIZED_APP_FILE_PATH);
-        if let Some(parent) = path.parent() {
-            if !parent.exists() {
-                std::fs::create_dir_all(parent).ok();
+    /// Sample the peak level for the VU meter (if enabled and the filter is running)
+    fn update_levels(&mut self, metadata: Option<HashMap<String, String>>) {
+        let level = match self.config.vu_meter && !self.is_paused {
+            true => metadata.and_then(|metadata| {
+                metadata
+                    .get("lavfi.astats.Overall.Peak_level")?
+                    .parse::<f32>()
+                    .ok()
+            }),
+            false => None,
+        };
+        match level {
+            Some(db) => {
+                let level = (1.0 - db / VU_METER_FLOOR_DB).clamp(0.0, 1.0);
+                self.levels
+                    .push_back(if level.is_nan() { 0.0 } else { level });
+                if self.levels.len() > VU_METER_LENGTH {
+                    self.levels.pop_front();
+                }
             }
+            None => self.levels.clear(),
+        }
+    }
+
+    /// Add the time since the last update if playback wasn't paused in the meantime
+    fn update_listening_time(&mut self) {
+        let now = Instant::now();
+        if let Some((last_update, station)) = self.last_update {
+            if station != self.station && self.config.reset_session_clock {
+                self.listening_time = Duration::ZERO;
+            } else if !self.is_paused && !self.is_stopped {
+                self.listening_time += now - last_update;
+            }
+        }
+        self.last_update = Some((now, self.station));
+    }
+
+    /// Whether mpv seems to have died
+    fn is_reconnecting(&self) -> bool {
+        self.ipc_failures >= IPC_FAILURE_THRESHOLD
+    }
+
+    /// Respawn the player, backing off in case it keeps dying
+    fn restart_player(&mut self) {
+        let now = Instant::now();
+        if self
+            .next_restart
+            .is_some_and(|next_restart| now < next_restart)
+        {
+            return;
+        }
+        log::warn!("The player stopped responding, restarting it");
+        if let Err(e) = self.ensure_playing() {
+            log::error!("Could not restart the player: {}", e);
+        }
+        self.next_restart = Some(now + self.restart_backoff);
+        self.restart_backoff = (self.restart_backoff * 2).min(MAX_RESTART_BACKOFF);
+    }
+
+    /// Switch to the next sleep timer duration (or turn it off)
+    fn cycle_sleep_timer(&mut self) {
+        self.sleep_timer_option = (self.sleep_timer_option + 1) % SLEEP_TIMER_OPTIONS.len();
+        self.sleep_deadline =
+            SLEEP_TIMER_OPTIONS[self.sleep_timer_option].map(|duration| Instant::now() + duration);
+    }
+
+    /// Pause playback once the sleep timer has elapsed
+    fn check_sleep_timer(&mut self) -> Result<()> {
+        if self
+            .sleep_deadline
+            .is_some_and(|deadline| deadline <= Instant::now())
+        {
+            self.sleep_timer_option = 0;
+            self.sleep_deadline = None;
+            mpv_set_property("pause", true)?;
+            self.is_paused = true;
         }
-        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
         Ok(())
     }
-}
 
-fn get_track_info() -> Result<Track> {
-    let track_info = mpv_get_property::<Track>("metadata")?;
-    // MPV appends successive metadata to the end of the string, separated by semicolons
-    let get_last = |s: String| s.split(";").last().unwrap().to_string();
-    Ok(Track {
-        title: get_last(track_info.title),
-        artist: get_last(track_info.artist),
-        album: get_last(track_info.album),
-    })
-}
+    fn show_error(&mut self, error: Box<dyn Error>) {
+        let message = error.to_string();
+        self.diagnostics
+            .push_front((chrono::Local::now(), message.clone()));
+        self.diagnostics.truncate(MAX_DIAGNOSTICS_LENGTH);
+        self.error = Some((message, Instant::now() + ERROR_TIMEOUT));
+    }
 
-fn ui<B: Backend>(f: &mut Frame<B>, app: &App) {
-    let size = f.size();
-    let block = Block::default()
-        .title(format!(" {} ", APP_TITLE))
-        .title_alignment(Alignment::Center)
-        .borders(tui::widgets::Borders::ALL)
-        .border_type(BorderType::Rounded);
-    f.render_widget(block, size);
-
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .margin(4)
-        .constraints(vec![Constraint::Min(1); 4])
-        .split(f.size());
-    f.render_widget(
-        Paragraph::new(Text::from(Spans::from(format!(
-            "Station: {}",
-            STATIONS[app.station]
-        )))),
-        chunks[0],
-    );
-    f.render_widget(
-        Paragraph::new(Text::from(Spans::from(format!(
-            "State:   {}",
-            match app.is_paused {
-                true => "paused",
-                false => "playing",
+    fn show_toast(&mut self, message: &str) {
+        self.toast = Some((message.to_string(), Instant::now() + TOAST_TIMEOUT));
+    }
+
+    fn change_volume(&mut self, change: f32) -> Result<()> {
+        let volume = mpv_get_property::<f32>("volume")?;
+        self.set_volume(volume + change)
+    }
+
+    /// Set the volume (clamped to the range that can currently be set)
+    fn set_volume(&mut self, volume: f32) -> Result<()> {
+        let volume = volume.clamp(0.0, self.max_volume());
+        mpv_set_property("volume", volume)?;
+        self.volume = volume;
+        // Adjusting the volume while muted discards the volume to restore
+        self.pre_mute_volume = None;
+        self.remember_station_volume();
+        Ok(())
+    }
+
+    /// The highest volume that can currently be set
+    fn max_volume(&self) -> f32 {
+        match (self.config.volume_scale, self.volume_boost) {
+            (config::VolumeScale::Percent, false) => MAX_UNBOOSTED_VOLUME,
+            _ => MAX_VOLUME,
+        }
+    }
+
+    fn toggle_volume_boost(&mut self) -> Result<()> {
+        if self.config.volume_scale != config::VolumeScale::Percent {
+            return Ok(());
+        }
+        self.volume_boost = !self.volume_boost;
+        match self.volume_boost {
+            true => self.show_toast("Volume boost on"),
+            false => {
+                self.show_toast("Volume boost off");
+                // Drop back into the regular range
+                let change = (self.volume - MAX_UNBOOSTED_VOLUME).max(0.0);
+                self.change_volume(-change)?;
             }
-        )))),
-        chunks[1],
-    );
-    f.render_widget(
-        Paragraph::new(Text::from(Spans::from(format!(
-            "Track:   {}",
-            match &app.current_track {
-                Some(track) => format!("{}", track),
-                None => "...".to_string(),
+        }
+        Ok(())
+    }
+
+    fn toggle_mute(&mut self) -> Result<()> {
+        match self.pre_mute_volume {
+            Some(volume) => {
+                mpv_set_property("volume", volume)?;
+                self.volume = volume;
+                self.pre_mute_volume = None;
             }
-        )))),
-        chunks[2],
-    );
-    f.render_widget(
-        Paragraph::new(Text::from(Spans::from(format!("Volume:  {}", app.volume)))),
-        chunks[3],
-    );
-}
+            None => {
+                mpv_set_property("volume", 0.0)?;
+                self.pre_mute_volume = Some(self.volume);
+                self.volume = 0.0;
+            }
+        }
+        Ok(())
+    }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
-    let mut next_poll = Instant::now();
-    loop {
-        // Debounce updates and be easy on the IO
-        if next_poll < Instant::now() {
-            // Synchronize app state with mpv (and perhaps start mpv if it's not running)
-            app.update();
-            next_poll = Instant::now() + POLLING_RATE;
+    /// Whether audio is (supposed to be) coming out, i.e. neither paused nor stopped
+    fn is_playing(&self) -> bool {
+        !self.is_paused && !self.is_stopped
+    }
+
+    /// Pause or resume, there is nothing to do while stopped (see `start_playback`)
+    fn toggle_pause(&mut self) -> Result<()> {
+        if self.is_stopped {
+            return Ok(());
+        }
+        // Ramp up from silence instead of resuming at full volume
+        let fade_in = self.is_paused && self.config.fade_in;
+        if fade_in {
+            mpv_set_property("volume", 0.0)?;
+        }
+        mpv_set_property("pause", !self.is_paused)?;
+        self.is_paused = !self.is_paused;
+        if fade_in {
+            fade_volume(0.0, self.volume);
+        }
+        Ok(())
+    }
+
+    /// Quit the player (unlike pausing, this also stops loading the stream)
+    fn stop(&mut self) -> Result<()> {
+        mpv_stop()?;
+        self.is_stopped = true;
+        self.is_buffering = false;
+        self.levels.clear();
+        Ok(())
+    }
+
+    /// Start a player for the current station after stopping
+    fn start_playback(&mut self) -> Result<()> {
+        self.ensure_playing()?;
+        if self.is_paused {
+            mpv_set_property_with_retry("pause", false)?;
         }
+        self.is_stopped = false;
+        self.is_paused = false;
+        Ok(())
+    }
 
-        // Update the UI
-        terminal.draw(|f| ui(f, &app))?;
-
-        // Handle events
-        let mut update_volume = |change: f32| -> Result<()> {
-            let volume = mpv_get_property::<f32>("volume")?;
-            let volume = (volume + change).max(0.0).min(150.0);
-            mpv_set_property("volume", volume)?;
-            app.volume = volume;
-            Ok(())
+    fn copy_track_to_clipboard(&mut self) -> Result<()> {
+        let Some(track) = &self.current_track else {
+            return Ok(());
         };
-        if let Event::Key(key) = event::read()? {
-            match key.code {
-                KeyCode::Char('q') | KeyCode::Esc => break,
-                KeyCode::Char('p') => {
-                    mpv_set_property("pause", !app.is_paused)?;
-                    app.is_paused = !app.is_paused;
-                }
-                KeyCode::Char('V') => {
-                    update_volume(5.0)?;
-                }
-                KeyCode::Char('v') => {
-                    update_volume(-5.0)?;
-                }
-                KeyCode::Char('y') => {
-                    app.current_track = get_track_info().ok();
-                    if let Some(track) = &app.current_track {
-                        track.search_yt_music();
-                    }
-                }
-                KeyCode::Char('n') => {
-                    app.station = (app.station + 1) % STATIONS.len();
-                    ensure_playing_station(app.station)?;
-                }
-                _ => {}
+        let clipboard = match &mut self.clipboard {
+            Some(clipboard) => clipboard,
+            None => self.clipboard.insert(arboard::Clipboard::new()?),
+        };
+        clipboard.set_text(format!("{} {}", track.title, track.artist))?;
+        self.show_toast("Copied to clipboard");
+        Ok(())
+    }
+
+    fn toggle_recording(&mut self) -> Result<()> {
+        match self.recording {
+            Some(_) => self.stop_recording(),
+            None => {
+                self.recording = Some(start_recording(
+                    &self.config.player_command,
+                    self.config.station_url(self.station).as_str(),
+                    &self.config.stations[self.station].name,
+                )?);
+                Ok(())
             }
         }
     }
-    Ok(())
-}
 
-fn main() -> Result<()> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    let mut app = App::load();
-    let res = run_app(&mut terminal, &mut app);
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
-    if let Err(e) = res {
-        eprintln!("Error: {}", e);
+    fn stop_recording(&mut self) -> Result<()> {
+        if let Some(mut recording) = self.recording.take() {
+            recording.kill()?;
+            recording.wait()?;
+        }
+        Ok(())
+    }
+
+    /// Make sure the current station is playing, with the audio filters applied to new players
+    fn ensure_playing(&self) -> Result<()> {
+        if ensure_playing_station(&self.config, self.station, self.volume)? {
+            mpv_set_property_with_retry("af", self.audio_filters(self.eq_preset))?;
+        }
+        Ok(())
+    }
+
+    /// mpv's filter chain for the given equalizer preset (and the VU meter if enabled)
+    fn audio_filters(&self, eq_preset: usize) -> String {
+        let vu_meter = if self.config.vu_meter {
+            VU_METER_FILTER
+        } else {
+            ""
+        };
+        [EQ_PRESETS[eq_preset].1, vu_meter]
+            .into_iter()
+            .filter(|filter| !filter.is_empty())
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+
+    /// Apply changes made to the config file
+    /// (the metadata feed, scrobbling and the integrations stay as they were on startup)
+    fn reload_config(&mut self) -> Result<()> {
+        self.apply_config(Config::load()?);
+        // Restarts the player if the URL of the station changed (stopping is only undone on request)
+        if !self.is_stopped {
+            self.ensure_playing()?;
+        }
+        self.show_toast("Config reloaded");
+        Ok(())
+    }
+
+    /// Switch to an updated config, keeping the current station if it is still listed
+    fn apply_config(&mut self, mut config: Config) {
+        let stations = std::mem::replace(
+            &mut config.stations,
+            std::mem::take(&mut self.config.stations),
+        );
+        self.config = config;
+        self.set_stations(stations);
+    }
+
+    /// Switch to an updated station list, keeping the current station if it is still listed
+    fn set_stations(&mut self, stations: Vec<config::Station>) {
+        let position = |station: usize| {
+            let name = &self.config.stations[station].name;
+            stations.iter().position(|station| &station.name == name)
+        };
+        let station = position(self.station);
+        let last_station = self.last_station.and_then(position);
+        self.config.stations = stations;
+        self.station = station.unwrap_or(0);
+        self.last_station = last_station;
+    }
+
+    fn cycle_eq_preset(&mut self) -> Result<()> {
+        let eq_preset = (self.eq_preset + 1) % EQ_PRESETS.len();
+        mpv_set_property("af", self.audio_filters(eq_preset))?;
+        self.eq_preset = eq_preset;
+        Ok(())
+    }
+
+    /// Show the station right away but only play it once no other one was selected for a moment,
+    /// so skipping through the stations doesn't spawn a player for each of them
+    fn select_station(&mut self, station: usize) {
+        // Skipping through the stations only counts as switching away from the first one
+        if self.station_switch_deadline.is_none() && station != self.station {
+            self.last_station = Some(self.station);
+        }
+        self.station = station;
+        self.station_switch_deadline = Some(Instant::now() + STATION_SWITCH_DELAY);
+    }
+
+    /// Play the selected station if it's due
+    fn apply_station_switch(&mut self) -> Result<()> {
+        if self
+            .station_switch_deadline
+            .is_some_and(|deadline| deadline <= Instant::now())
+        {
+            self.station_switch_deadline = None;
+            self.restore_station_volume();
+            self.ensure_playing()?;
+        }
+        Ok(())
+    }
+
+    /// Use the volume last set on the current station (if enabled)
+    fn restore_station_volume(&mut self) {
+        if !self.config.per_station_volume {
+            return;
+        }
+        let name = &self.config.stations[self.station].name;
+        if let Some(&volume) = self.station_volumes.get(name) {
+            self.volume = volume;
+            self.pre_mute_volume = None;
+        }
+    }
+
+    /// Remember the volume for the current station (if enabled)
+    fn remember_station_volume(&mut self) {
+        if self.config.per_station_volume {
+            let name = self.config.stations[self.station].name.clone();
+            self.station_volumes.insert(name, self.volume);
+        }
+    }
+
+    /// Switch to the station right away
+    fn play_station(&mut self, station: usize) -> Result<()> {
+        if station != self.station {
+            self.last_station = Some(self.station);
+        }
+        self.station = station;
+        self.station_switch_deadline = None;
+        self.restore_station_volume();
+        self.ensure_playing()
+    }
+
+    /// Start a fresh player for the current station, even if it seems to be playing fine,
+    /// since a stale stream buffer can make the audio stutter indefinitely
+    fn reconnect(&mut self) -> Result<()> {
+        self.reconnect_pending = false;
+        log::info!(
+            "Reconnecting to station {}",
+            self.config.stations[self.station].name
+        );
+        let stopped_pid = mpv_stop()?;
+        mpv_start(
+            &self.config.player_command,
+            self.config.station_url(self.station).as_str(),
+            self.volume,
+        )?;
+        if !wait_for_player(stopped_pid) {
+            return Err("The player did not start in time".into());
+        }
+        mpv_set_property_with_retry("af", self.audio_filters(self.eq_preset))?;
+        self.is_paused = false;
+        Ok(())
+    }
+
+    /// Switch back to the station selected before the current one
+    fn toggle_last_station(&mut self) -> Result<()> {
+        match self.last_station {
+            Some(last_station) => self.play_station(last_station),
+            None => Ok(()),
+        }
+    }
+
+    fn next_station(&mut self) {
+        self.select_station((self.station + 1) % self.config.stations.len());
+    }
+
+    fn previous_station(&mut self) {
+        let station_count = self.config.stations.len();
+        self.select_station((self.station + station_count - 1) % station_count);
+    }
+
+    /// Switch to any station but the current one right away
+    fn random_station(&mut self) -> Result<()> {
+        let station_count = self.config.stations.len();
+        if station_count < 2 {
+            return Ok(());
+        }
+        let offset = rand::thread_rng().gen_range(1..station_count);
+        self.play_station((self.station + offset) % station_count)
+    }
+
+    /// Where the state is stored, `None` disables persistence (if there is neither a home
+    /// nor a data directory, rather than using a surprising location like the working directory)
+    fn path(home: Option<PathBuf>, data_dir: Option<OsString>) -> Option<PathBuf> {
+        match home {
+            Some(home) => Some(home.join(USER_SERIALIZED_APP_FILE_PATH)),
+            None => {
+                data_dir.map(|data_dir| PathBuf::from(data_dir).join(DATA_SERIALIZED_APP_FILE_PATH))
+            }
+        }
+    }
+
+    /// Load the state and the config, the scrobbler and the metadata feed are only started
+    /// by `load`
+    fn load_state() -> Self {
+        let path = Self::path(home_dir(), std::env::var_os("XDG_DATA_HOME"));
+        if path.is_none() {
+            log::warn!("Could not get home or data directory, the state won't be saved");
+        }
+        let mut is_corrupt = false;
+        let content = path
+            .as_ref()
+            .map(|path| (path, std::fs::read_to_string(path)));
+        let mut app: Self = match content {
+            Some((path, Ok(content))) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                // Keep the broken state around instead of overwriting it on exit
+                log::warn!("Could not parse {}, resetting it: {}", path.display(), e);
+                std::fs::rename(path, path.with_extension("json.bak")).ok();
+                is_corrupt = true;
+                Self::default()
+            }),
+            _ => Self::default(), // Nothing stored yet
+        };
+        if is_corrupt {
+            app.toast = Some((
+                "The saved state was corrupt and has been reset (see app.json.bak)".into(),
+                Instant::now() + ERROR_TIMEOUT,
+            ));
+        } else if path.is_none() {
+            app.toast = Some((
+                "Neither a home nor a data directory was found, the state won't be saved".into(),
+                Instant::now() + ERROR_TIMEOUT,
+            ));
+        }
+        // The track from the last session is stale (and must not trigger a notification)
+        app.current_track = None;
+        let (config, config_error) = Config::load_or_default();
+        app.config = config;
+        if let Some(config_error) = config_error {
+            app.show_error(config_error.into());
+        }
+        app.compact = app.config.compact;
+        app.use_color = supports_color();
+        if let Some(ipc_socket) = &app.config.ipc_socket {
+            platform::set_ipc_endpoint(ipc_socket.clone());
+        }
+        // The station list might have changed since the app was last stored
+        app.clamp_stations();
+        if app.eq_preset >= EQ_PRESETS.len() {
+            app.eq_preset = 0;
+        }
+        app
+    }
+
+    fn load() -> Self {
+        let mut app = Self::load_state();
+        app.scrobbler = app.config.lastfm.clone().map(lastfm::Scrobbler::new);
+        app.metadata_feed = app
+            .config
+            .metadata_url
+            .clone()
+            .map(metadata::Feed::subscribe);
+        app
+    }
+
+    /// Take over the station (and its volume) from the running player
+    fn sync_station(&mut self) -> Result<()> {
+        if let Some(station) = self
+            .config
+            .station_by_path(&mpv_get_property::<String>("path")?)
+        {
+            self.station = station;
+        }
+        self.volume = mpv_get_property("volume")?;
+        Ok(())
+    }
+
+    /// Make sure the stations are still in the station list
+    fn clamp_stations(&mut self) {
+        let station_count = self.config.stations.len();
+        if self.station >= station_count {
+            self.station = 0;
+        }
+        if self
+            .last_station
+            .is_some_and(|station| station >= station_count)
+        {
+            self.last_station = None;
+        }
+    }
+
+    /// The configured colors (unless the terminal shouldn't get any)
+    fn theme(&self) -> &Theme {
+        match self.use_color {
+            true => &self.config.theme,
+            false => &Theme::COLORLESS,
+        }
+    }
+
+    /// Style for a fixed color (e.g. red for errors)
+    fn color(&self, color: Color) -> Style {
+        match self.use_color {
+            true => Style::default().fg(color),
+            false => Style::default(),
+        }
+    }
+
+    /// Make the player reflect the loaded state
+    fn start(&self) {
+        self.ensure_playing().ok();
+        // The player might have been started with different filters (e.g. by an older session)
+        mpv_set_property_with_retry("af", self.audio_filters(self.eq_preset)).ok();
+        // Restore the volume and paused state from the last session
+        mpv_set_property_with_retry("volume", self.volume).ok();
+        if self.is_paused {
+            mpv_set_property_with_retry("pause", true).ok();
+        }
+    }
+
+    fn store(&self) -> Result<()> {
+        let Some(path) = Self::path(home_dir(), std::env::var_os("XDG_DATA_HOME")) else {
+            return Ok(()); // Persistence is disabled (see `load`)
+        };
+        let mut state = serde_json::to_value(self)?;
+        // Being muted isn't restored, so the next start would be silent otherwise
+        if let Some(volume) = self.pre_mute_volume {
+            state["volume"] = serde_json::json!(volume);
+        }
+        write_atomically(&path, &serde_json::to_string_pretty(&state)?)
+    }
+}
+
+/// Replace the file's content without ever leaving it half-written (e.g. on a crash or full disk)
+/// by writing to a temporary file next to it first
+fn write_atomically(path: &Path, content: &str) -> Result<()> {
+    // Make path if it doesn't exist
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let mut file = File::create(&temp_path)?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
+    std::fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// Get the most recent value of a metadata field.
+/// MPV appends successive metadata to the end of the string, separated by semicolons.
+fn last_metadata_value(s: &str) -> String {
+    s.rsplit(';')
+        .map(str::trim)
+        .find(|segment| !segment.is_empty())
+        .unwrap_or(s)
+        .to_string()
+}
+
+/// Get the track from the stream metadata
+/// Titles like "Artist - Title" are split at the first of the separators if there is no artist
+fn get_track_info(separators: &[String]) -> Result<Track> {
+    Ok(parse_track(mpv_get_property("metadata")?, separators))
+}
+
+/// Clean up the track from the stream metadata (see `get_track_info`)
+fn parse_track(track_info: Track, separators: &[String]) -> Track {
+    let title = last_metadata_value(&track_info.title);
+    let artist = last_metadata_value(&track_info.artist);
+    let split_title = separators
+        .iter()
+        .find_map(|separator| title.split_once(separator.as_str()))
+        .filter(|_| artist.is_empty());
+    let (title, artist) = match split_title {
+        Some((artist, title)) => (title.trim().to_string(), artist.trim().to_string()),
+        None => (title, artist),
+    };
+    Track {
+        title,
+        artist,
+        album: last_metadata_value(&track_info.album),
+    }
+}
+
+/// Compute a rect of the given size centered in `area` (clipped to fit)
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+fn bordered_block<'a>(title: &'a str, theme: &Theme) -> Block<'a> {
+    Block::default()
+        .title(Span::styled(title, theme.title()))
+        .title_alignment(Alignment::Center)
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .border_style(theme.border())
+}
+
+/// Scrollable, since the keybindings don't fit into small terminals
+/// Returns the scroll offset, limited to the lines that are out of view
+fn help_popup<B: Backend>(f: &mut Frame<B>, keymap: &Keymap, scroll: u16, theme: &Theme) -> u16 {
+    let keybindings: Vec<(String, &str)> = KEYBINDINGS
+        .iter()
+        .map(|&(keys, action)| (active_keys(keys, keymap), action))
+        .collect();
+    let key_width = keybindings
+        .iter()
+        .map(|(k, _)| k.chars().count())
+        .max()
+        .unwrap_or(0);
+    let lines: Vec<Spans> = keybindings
+        .iter()
+        .map(|(key, action)| Spans::from(format!("{:<width$}  {}", key, action, width = key_width)))
+        .collect();
+    let width = lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16 + 4; // Borders + padding
+    let height = lines.len() as u16 + 2; // Borders
+    let area = centered_rect(width, height, f.size());
+    let hidden_lines = height - area.height;
+    let scroll = scroll.min(hidden_lines);
+    let title = match hidden_lines {
+        0 => " Help ".to_string(),
+        _ => format!(" Help ({}/{}) ", scroll + 1, hidden_lines + 1),
+    };
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(Text::from(lines))
+            .block(bordered_block(&title, theme))
+            .scroll((scroll, 0)),
+        area,
+    );
+    scroll
+}
+
+/// Keys like `p/Space` as they are mapped in the keymap
+fn active_keys(keys: &str, keymap: &Keymap) -> String {
+    keys.split('/')
+        .map(|key| {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(key), None) => keymap.active_key(key).to_string(),
+                _ => key.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn history_popup<B: Backend>(f: &mut Frame<B>, history: &VecDeque<Track>, theme: &Theme) {
+    let size = f.size();
+    let area = centered_rect(size.width * 4 / 5, size.height * 4 / 5, size);
+    let items: Vec<ListItem> = match history.is_empty() {
+        true => vec![ListItem::new("Nothing played yet")],
+        false => history
+            .iter()
+            .map(|track| ListItem::new(format!("{}", track)))
+            .collect(),
+    };
+    f.render_widget(Clear, area);
+    f.render_widget(
+        List::new(items).block(bordered_block(" History ", theme)),
+        area,
+    );
+}
+
+fn diagnostics_popup<B: Backend>(
+    f: &mut Frame<B>,
+    diagnostics: &VecDeque<(chrono::DateTime<chrono::Local>, String)>,
+    theme: &Theme,
+) {
+    let size = f.size();
+    let area = centered_rect(size.width * 4 / 5, size.height * 4 / 5, size);
+    let items: Vec<ListItem> = match diagnostics.is_empty() {
+        true => vec![ListItem::new("No errors")],
+        false => diagnostics
+            .iter()
+            .map(|(time, message)| {
+                ListItem::new(format!("{} {}", time.format("%H:%M:%S"), message))
+            })
+            .collect(),
+    };
+    f.render_widget(Clear, area);
+    f.render_widget(
+        List::new(items).block(bordered_block(" Diagnostics ", theme)),
+        area,
+    );
+}
+
+fn metadata_popup<B: Backend>(f: &mut Frame<B>, metadata: &str, theme: &Theme) {
+    let size = f.size();
+    let area = centered_rect(size.width * 4 / 5, size.height * 4 / 5, size);
+    f.render_widget(Clear, area);
+    f.render_widget(
+        Paragraph::new(metadata).block(bordered_block(" Metadata ", theme)),
+        area,
+    );
+}
+
+fn station_picker_popup<B: Backend>(
+    f: &mut Frame<B>,
+    config: &Config,
+    current: usize,
+    cursor: usize,
+    theme: &Theme,
+) {
+    let size = f.size();
+    let area = centered_rect(size.width * 4 / 5, size.height * 4 / 5, size);
+    let items: Vec<ListItem> = config
+        .stations
+        .iter()
+        .enumerate()
+        .map(|(i, station)| {
+            let marker = if i == current { "▶ " } else { "  " };
+            ListItem::new(format!("{}{}", marker, station))
+        })
+        .collect();
+    let mut state = ListState::default();
+    state.select(Some(cursor));
+    f.render_widget(Clear, area);
+    f.render_stateful_widget(
+        List::new(items)
+            .block(bordered_block(" Stations ", theme))
+            .highlight_style(theme.title().add_modifier(Modifier::REVERSED)),
+        area,
+        &mut state,
+    );
+}
+
+/// The track as displayed in `width` characters, shortening the album if it's too long
+/// (title and artist scroll instead)
+fn track_line(track: &Track, width: usize) -> String {
+    let without_album = format!("{} by {}", track.title, track.artist);
+    if track.album.is_empty() {
+        return without_album;
+    }
+    // Make room for " (" and ")"
+    let album_width = width.saturating_sub(without_album.chars().count() + 3);
+    if album_width < MIN_ALBUM_CHARS {
+        return without_album;
+    }
+    format!(
+        "{} ({})",
+        without_album,
+        truncate(&track.album, album_width)
+    )
+}
+
+/// Shorten the text to at most `width` characters, ending in an ellipsis if it was cut
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(width.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Get the `width` characters of `text` visible at the scroll position `offset`,
+/// wrapping around with a separator
+fn marquee(text: &str, width: usize, offset: usize) -> String {
+    let cycle: Vec<char> = text.chars().chain(MARQUEE_SEPARATOR.chars()).collect();
+    (0..width)
+        .map(|i| cycle[(offset + i) % cycle.len()])
+        .collect()
+}
+
+/// The volume as displayed (the underlying value is more precise)
+fn volume_percent(volume: f32) -> u32 {
+    volume.clamp(0.0, MAX_VOLUME).round() as u32
+}
+
+/// Format the duration as `HH:MM:SS`
+fn format_clock(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// Shown instead of the track while there is none
+fn track_placeholder(app: &App) -> &'static str {
+    match app.metadata_missing_since {
+        Some(since) if since.elapsed() >= METADATA_TIMEOUT => "no metadata",
+        Some(_) => "connecting…",
+        None => "...",
+    }
+}
+
+fn state_label(app: &App) -> &'static str {
+    if app.is_stopped {
+        return "stopped";
+    }
+    match (app.is_reconnecting(), app.is_paused, app.is_buffering) {
+        (true, _, _) => "disconnected (reconnecting…)",
+        (false, true, _) => "paused",
+        (false, false, true) => "buffering",
+        (false, false, false) => "playing",
+    }
+}
+
+fn state_line(app: &App) -> Vec<Span<'static>> {
+    let connectivity = match (app.is_stopped, app.is_reconnecting()) {
+        (true, _) => app.color(Color::DarkGray),
+        (false, true) => app.color(Color::Red),
+        (false, false) => app.color(Color::Green),
+    };
+    let mut spans = vec![Span::raw("State:   "), Span::styled("● ", connectivity)];
+    spans.push(Span::raw(state_label(app)));
+    let is_connecting = !app.is_paused
+        && !app.is_stopped
+        && !app.is_reconnecting()
+        && (app.is_buffering
+            || app
+                .metadata_missing_since
+                .is_some_and(|since| since.elapsed() < METADATA_TIMEOUT));
+    if is_connecting {
+        let frame = SPINNER_FRAMES[app.spinner_frame % SPINNER_FRAMES.len()];
+        spans.push(Span::styled(format!(" {}", frame), app.theme().title()));
+    }
+    // Only worth mentioning if updates are overdue
+    if let Some(last_sync) = app.last_sync {
+        let elapsed = last_sync.elapsed();
+        if elapsed > app.config.polling_rate() * 2 {
+            spans.push(Span::raw(format!(" (updated {}s ago)", elapsed.as_secs())));
+        }
+    }
+    if let Some(deadline) = app.sleep_deadline {
+        let remaining = deadline.saturating_duration_since(Instant::now()).as_secs();
+        spans.push(Span::raw(format!(
+            " (sleep in {}:{:02})",
+            remaining / 60,
+            remaining % 60
+        )));
+    }
+    spans.push(Span::raw(format!(
+        " · {}",
+        format_clock(app.listening_time)
+    )));
+    if app.eq_preset != 0 {
+        spans.push(Span::raw(format!(" · EQ: {}", EQ_PRESETS[app.eq_preset].0)));
+    }
+    if app.recording.is_some() {
+        spans.push(Span::styled(" ● REC", app.color(Color::Red)));
+    }
+    if !app.levels.is_empty() {
+        let meter: String = app
+            .levels
+            .iter()
+            .map(|level| {
+                let index = (level * (VU_METER_LEVELS.len() - 1) as f32).round() as usize;
+                VU_METER_LEVELS[index]
+            })
+            .collect();
+        spans.push(Span::raw(" · "));
+        spans.push(Span::styled(meter, app.theme().gauge()));
+    }
+    spans
+}
+
+/// Colors are left out if `NO_COLOR` is set (see https://no-color.org) or the terminal is dumb
+fn supports_color() -> bool {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let is_dumb = std::env::var("TERM").is_ok_and(|term| term == "dumb");
+    !no_color && !is_dumb
+}
+
+/// Split the screen into the station, state, track, volume, status and banner areas
+/// The banner (on top) is only shown on big screens, while on small screens the margin is
+/// dropped and the areas are stacked in order of importance, leaving the ones that don't fit empty
+fn layout(area: Rect) -> Rc<[Rect]> {
+    let is_roomy = area.height >= MIN_ROOMY_HEIGHT && area.width >= MIN_ROOMY_WIDTH;
+    if is_roomy {
+        let has_banner = area.height >= MIN_BANNER_HEIGHT;
+        let banner_height = if has_banner { BANNER.len() as u16 } else { 0 };
+        let outer = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(4)
+            .constraints(vec![Constraint::Length(banner_height), Constraint::Min(0)])
+            .split(area);
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(vec![
+                Constraint::Min(1),
+                Constraint::Min(1),
+                Constraint::Min(1),
+                Constraint::Min(3), // Room for the bordered volume gauge
+                Constraint::Length(1),
+            ])
+            .split(outer[1]);
+        return chunks.iter().chain(&outer[..1]).copied().collect();
+    }
+    // Stay inside the outer border
+    let inner = area.inner(&Margin {
+        vertical: 1,
+        horizontal: 1,
+    });
+    (0..5)
+        .map(|row| {
+            let y = (inner.y + row).min(inner.bottom());
+            Rect::new(inner.x, y, inner.width, (row < inner.height) as u16)
+        })
+        .chain([Rect::default()]) // No banner
+        .collect()
+}
+
+/// Everything on one line, e.g. `♪ chillsynth | playing | Title — Artist | 80%`
+/// (followed by the status if there is one)
+fn compact_line(app: &App) -> Vec<Span<'static>> {
+    let track = match &app.current_track {
+        Some(track) if track.artist.is_empty() => track.title.clone(),
+        Some(track) => format!("{} — {}", track.title, track.artist),
+        None => track_placeholder(app).to_string(),
+    };
+    let mut spans = vec![Span::raw(format!(
+        "♪ {} | {} | {} | {}%",
+        app.config.stations[app.station].name,
+        state_label(app),
+        track,
+        volume_percent(app.volume)
+    ))];
+    if app.config.clock {
+        spans.push(Span::raw(format!(" | {}", clock())));
+    }
+    if let Some(status) = status_line(app) {
+        spans.push(Span::raw(" | "));
+        spans.push(status);
+    }
+    spans
+}
+
+/// The current local time, e.g. `▶ 23:42 ◀`
+fn clock() -> String {
+    chrono::Local::now().format("▶ %H:%M ◀").to_string()
+}
+
+/// The volume being entered, the quit confirmation, the last error or feedback on the last action (in that order)
+fn status_line(app: &App) -> Option<Span<'static>> {
+    if let Some(input) = &app.volume_input {
+        return Some(Span::raw(format!(
+            "Volume: {}_ (Enter to set, Esc to cancel)",
+            input
+        )));
+    }
+    match (&app.confirm_quit_until, &app.error, &app.toast) {
+        (Some(_), _, _) => Some(Span::raw(format!(
+            "Press {} again to quit",
+            app.config.keymap.active_key('q')
+        ))),
+        (None, Some((error, _)), _) => Some(Span::styled(
+            format!("Error: {}", error),
+            app.color(Color::Red),
+        )),
+        (None, None, Some((toast, _))) => Some(Span::styled(toast.clone(), app.theme().title())),
+        (None, None, None) => None,
+    }
+}
+
+fn ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let size = f.size();
+    #[cfg(feature = "logo")]
+    app.logo_panel.hide();
+    if app.compact {
+        let line = Rect::new(size.x, size.y, size.width, size.height.min(1));
+        f.render_widget(
+            Paragraph::new(Text::from(Spans::from(compact_line(app)))),
+            line,
+        );
+    } else {
+        full_ui(f, app);
+    }
+    // Images are drawn on top of the text, so the logo has to make way for the popups
+    #[cfg(feature = "logo")]
+    if app.show_history
+        || app.show_diagnostics
+        || app.metadata_dump.is_some()
+        || app.station_picker.is_some()
+        || app.show_help
+    {
+        app.logo_panel.hide();
+    }
+
+    let theme = app.theme();
+    if app.show_history {
+        history_popup(f, &app.history, theme);
+    }
+    if app.show_diagnostics {
+        diagnostics_popup(f, &app.diagnostics, theme);
+    }
+    if let Some(metadata) = &app.metadata_dump {
+        metadata_popup(f, metadata, theme);
+    }
+    if let Some(cursor) = app.station_picker {
+        station_picker_popup(f, &app.config, app.station, cursor, theme);
+    }
+    if app.show_help {
+        app.help_scroll = help_popup(f, &app.config.keymap, app.help_scroll, theme);
+    }
+}
+
+/// The station, state, track, volume and status on separate lines within a border
+fn full_ui<B: Backend>(f: &mut Frame<B>, app: &mut App) {
+    let size = f.size();
+    let theme = &app.theme().clone();
+    let title = format!(" {} ", APP_TITLE);
+    f.render_widget(bordered_block(&title, theme), size);
+
+    let chunks = layout(size);
+    f.render_widget(
+        Paragraph::new(Text::from(Spans::from(format!(
+            "Station: {}",
+            app.config.stations[app.station]
+        )))),
+        chunks[0],
+    );
+    if app.config.clock {
+        f.render_widget(
+            Paragraph::new(Span::styled(clock(), theme.title())).alignment(Alignment::Right),
+            chunks[0],
+        );
+    }
+    f.render_widget(
+        Paragraph::new(Text::from(Spans::from(state_line(app)))),
+        chunks[1],
+    );
+    let track_label = "Track:   ";
+    let track_width = (chunks[2].width as usize).saturating_sub(track_label.len());
+    let track = match &app.current_track {
+        Some(track) if size.width < MIN_ALBUM_WIDTH => {
+            format!("{} by {}", track.title, track.artist)
+        }
+        Some(track) => track_line(track, track_width),
+        None => track_placeholder(app).to_string(),
+    };
+    let track = if track.chars().count() > track_width {
+        marquee(&track, track_width, app.marquee_offset)
+    } else {
+        app.marquee_offset = 0;
+        track
+    };
+    let is_new_track = app
+        .track_changed_at
+        .is_some_and(|changed_at| changed_at.elapsed() < TRACK_HIGHLIGHT_DURATION);
+    let track_style = match is_new_track {
+        true => theme.title().add_modifier(Modifier::BOLD),
+        false => Style::default(),
+    };
+    f.render_widget(
+        Paragraph::new(Text::from(Spans::from(vec![
+            Span::raw(track_label),
+            Span::styled(track, track_style),
+        ]))),
+        chunks[2],
+    );
+    let volume = Gauge::default()
+        .gauge_style(theme.gauge())
+        .ratio((app.volume / app.max_volume()).clamp(0.0, 1.0) as f64);
+    // Without room for the border, the title moves into the label
+    let volume = if chunks[3].height >= 3 {
+        volume
+            .block(bordered_block("Volume", theme).title_alignment(Alignment::Left))
+            .label(format!("{}%", volume_percent(app.volume)))
+    } else {
+        volume.label(format!("Volume {}%", volume_percent(app.volume)))
+    };
+    f.render_widget(volume, chunks[3]);
+    if let Some(status) = status_line(app) {
+        f.render_widget(Paragraph::new(Text::from(Spans::from(status))), chunks[4]);
+    }
+    #[cfg(feature = "logo")]
+    if app.logo_panel.is_supported() {
+        // The album art of the current track, else the logo of the station
+        let station = &app.config.stations[app.station];
+        let art = app
+            .metadata_feed
+            .as_ref()
+            .and_then(|feed| feed.art(&station.name));
+        let image = art
+            .and_then(|art| app.logos.get(&art))
+            .or_else(|| station.logo.as_ref().and_then(|logo| app.logos.get(logo)));
+        if let Some(image) = image {
+            app.logo_panel.show(image, chunks[5]);
+            return;
+        }
+    }
+    let banner: Vec<Spans> = BANNER
+        .iter()
+        .map(|line| Spans::from(Span::styled(*line, theme.title())))
+        .collect();
+    f.render_widget(
+        Paragraph::new(banner).alignment(Alignment::Center),
+        chunks[5],
+    );
+}
+
+/// Handle a key press
+/// Returns `true` if the app should quit
+fn handle_key(app: &mut App, key: KeyEvent) -> Result<bool> {
+    if let Some(cursor) = app.station_picker {
+        handle_station_picker_key(app, cursor, key)?;
+        return Ok(false);
+    }
+    if let Some(input) = &mut app.volume_input {
+        match key.code {
+            KeyCode::Char(digit @ '0'..='9') if input.len() < MAX_VOLUME_INPUT_LENGTH => {
+                input.push(digit)
+            }
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Enter => {
+                let volume = input.parse::<f32>().ok();
+                app.volume_input = None;
+                if let Some(volume) = volume {
+                    app.set_volume(volume)?;
+                }
+            }
+            KeyCode::Esc => app.volume_input = None,
+            _ => {}
+        }
+        return Ok(false);
+    }
+    // Map custom keys to the default ones handled below
+    let key_code = match key.code {
+        KeyCode::Char(c) => match app.config.keymap.translate(c) {
+            Some(c) => KeyCode::Char(c),
+            None => return Ok(false),
+        },
+        key_code => key_code,
+    };
+    let volume_step = app.config.volume_step;
+    match key_code {
+        KeyCode::Char('l') => app.station_picker = Some(app.station),
+        KeyCode::Char('?') => {
+            app.show_help = !app.show_help;
+            app.help_scroll = 0;
+        }
+        KeyCode::Up if app.show_help => app.help_scroll = app.help_scroll.saturating_sub(1),
+        KeyCode::Down if app.show_help => app.help_scroll = app.help_scroll.saturating_add(1),
+        KeyCode::Char('h') => app.show_history = !app.show_history,
+        KeyCode::Char('D') => app.show_diagnostics = !app.show_diagnostics,
+        KeyCode::Delete if app.show_diagnostics => app.diagnostics.clear(),
+        KeyCode::Char('i') => {
+            app.metadata_dump = match app.metadata_dump {
+                Some(_) => None,
+                // Everything the station sends, to see why the track might look off
+                None => Some(match mpv_get_property::<serde_json::Value>("metadata") {
+                    Ok(metadata) => serde_json::to_string_pretty(&metadata)?,
+                    Err(e) => format!("No metadata available ({})", e),
+                }),
+            }
+        }
+        KeyCode::Esc
+            if app.show_help
+                || app.show_history
+                || app.show_diagnostics
+                || app.metadata_dump.is_some() =>
+        {
+            app.show_help = false;
+            app.show_history = false;
+            app.show_diagnostics = false;
+            app.metadata_dump = None;
+        }
+        KeyCode::Char('q') | KeyCode::Esc => {
+            // Only ask for confirmation if quitting would interrupt listening
+            if app.config.confirm_quit && app.is_playing() && app.confirm_quit_until.is_none() {
+                app.confirm_quit_until = Some(Instant::now() + QUIT_CONFIRMATION_TIMEOUT);
+            } else {
+                if app.config.stop_on_quit {
+                    mpv_stop()?;
+                }
+                return Ok(true);
+            }
+        }
+        KeyCode::Char('Q') => {
+            mpv_stop()?;
+            return Ok(true);
+        }
+        // Also the quickest way to get going again after stopping
+        KeyCode::Char('p' | ' ') if app.is_stopped => app.start_playback()?,
+        KeyCode::Char('p' | ' ') => app.toggle_pause()?,
+        KeyCode::Char('t') => app.stop()?,
+        KeyCode::Char('T') => app.start_playback()?,
+        KeyCode::Char('V') => app.change_volume(volume_step)?,
+        KeyCode::Char('v') => app.change_volume(-volume_step)?,
+        KeyCode::Char('+') => app.change_volume(FINE_VOLUME_STEP)?,
+        KeyCode::Char('-') => app.change_volume(-FINE_VOLUME_STEP)?,
+        KeyCode::Char('0') => app.set_volume(0.0)?,
+        KeyCode::Char(')') => app.set_volume(MAX_UNBOOSTED_VOLUME)?,
+        KeyCode::Char('=') => app.volume_input = Some(String::new()),
+        KeyCode::Char('m') => app.toggle_mute()?,
+        KeyCode::Char('B') => app.toggle_volume_boost()?,
+        KeyCode::Char('s') => app.cycle_sleep_timer(),
+        KeyCode::Char('w') => app.toggle_recording()?,
+        KeyCode::Char('e') => app.cycle_eq_preset()?,
+        KeyCode::Char('r') => app.reload_config()?,
+        KeyCode::Char('C') => app.compact = !app.compact,
+        KeyCode::Char('d') => {
+            app.notifications = !app.notifications;
+            match app.notifications {
+                true => app.show_toast("Notifications on"),
+                false => app.show_toast("Notifications off"),
+            }
+        }
+        KeyCode::Char(key @ ('y' | 'S' | 'b' | 'g')) => {
+            let service = match key {
+                'y' => SearchService::YouTubeMusic,
+                'S' => SearchService::Spotify,
+                'b' => SearchService::Bandcamp,
+                _ => SearchService::Web,
+            };
+            app.current_track = get_track_info(&app.config.title_separators).ok();
+            if let Some(track) = &app.current_track {
+                track.search(service);
+            }
+        }
+        KeyCode::Char('c') => app.copy_track_to_clipboard()?,
+        KeyCode::Char('o') => {
+            // Hand the stream to whatever is registered for it (e.g. the browser or VLC)
+            let url = app.config.station_url(app.station);
+            platform::open_url(&url);
+            app.show_toast(&url);
+        }
+        KeyCode::Char('f') => {
+            if let Some(track) = &app.current_track {
+                match track.add_to_favorites()? {
+                    true => app.show_toast("Saved ♥"),
+                    false => app.show_toast("Already a favorite"),
+                }
+            }
+        }
+        KeyCode::Char('n') => app.next_station(),
+        KeyCode::Char('N') => app.previous_station(),
+        KeyCode::Backspace => app.toggle_last_station()?,
+        KeyCode::Char('R') => app.random_station()?,
+        KeyCode::Char('x') => {
+            app.reconnect_pending = true;
+            app.show_toast("Reconnecting…");
+        }
+        KeyCode::Char(digit @ '1'..='9') => {
+            // Ignore digits that don't map to a station
+            let station = digit as usize - '1' as usize;
+            if station < app.config.stations.len() {
+                app.select_station(station);
+            }
+        }
+        _ => {}
+    }
+    Ok(false)
+}
+
+/// Handle a key press while the station picker is open
+fn handle_station_picker_key(app: &mut App, cursor: usize, key: KeyEvent) -> Result<()> {
+    let station_count = app.config.stations.len();
+    match key.code {
+        KeyCode::Up | KeyCode::Char('k') => {
+            app.station_picker = Some((cursor + station_count - 1) % station_count);
+        }
+        KeyCode::Down | KeyCode::Char('j') => {
+            app.station_picker = Some((cursor + 1) % station_count);
+        }
+        KeyCode::Enter => {
+            app.station_picker = None;
+            app.play_station(cursor)?;
+        }
+        KeyCode::Esc | KeyCode::Char('l') => app.station_picker = None,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Handle a mouse event on a screen of the given size
+fn handle_mouse(app: &mut App, mouse: MouseEvent, size: Rect) -> Result<()> {
+    // Only the full view has areas to click
+    if app.compact
+        || app.show_help
+        || app.show_history
+        || app.show_diagnostics
+        || app.metadata_dump.is_some()
+        || app.station_picker.is_some()
+    {
+        return Ok(());
+    }
+    let chunks = layout(size);
+    let is_over = |area: Rect| {
+        (area.left()..area.right()).contains(&mouse.column)
+            && (area.top()..area.bottom()).contains(&mouse.row)
+    };
+    let volume_step = app.config.volume_step;
+    match mouse.kind {
+        MouseEventKind::ScrollUp if is_over(chunks[3]) => app.change_volume(volume_step),
+        MouseEventKind::ScrollDown if is_over(chunks[3]) => app.change_volume(-volume_step),
+        MouseEventKind::Down(MouseButton::Left) if is_over(chunks[0]) => {
+            app.next_station();
+            Ok(())
+        }
+        MouseEventKind::Down(MouseButton::Right) if is_over(chunks[0]) => {
+            app.previous_station();
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn handle_mpris_command(app: &mut App, command: mpris::Command) -> Result<()> {
+    match command {
+        mpris::Command::PlayPause => app.toggle_pause(),
+        mpris::Command::Play if app.is_paused => app.toggle_pause(),
+        mpris::Command::Pause if app.is_playing() => app.toggle_pause(),
+        mpris::Command::Next => {
+            app.next_station();
+            Ok(())
+        }
+        mpris::Command::Previous => {
+            app.previous_station();
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(feature = "http")]
+fn handle_http_command(app: &mut App, command: http::Command) -> Result<()> {
+    match command {
+        http::Command::Play if app.is_paused => app.toggle_pause(),
+        http::Command::Pause if app.is_playing() => app.toggle_pause(),
+        http::Command::Toggle => app.toggle_pause(),
+        http::Command::Next => {
+            app.next_station();
+            Ok(())
+        }
+        http::Command::Previous => {
+            app.previous_station();
+            Ok(())
+        }
+        http::Command::Volume(cli::VolumeChange::By(change)) => app.change_volume(change),
+        http::Command::Volume(cli::VolumeChange::To(volume)) => app.set_volume(volume),
+        _ => Ok(()),
+    }
+}
+
+fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> Result<()> {
+    // Media key support is optional since it requires a D-Bus session bus
+    let mpris = match app.config.mpris {
+        true => mpris::Mpris::start().ok(),
+        false => None,
+    };
+    // Pausing on suspend is optional since it requires systemd-logind
+    #[cfg(target_os = "linux")]
+    let sleep_watcher = match app.config.pause_on_sleep {
+        true => sleep::SleepWatcher::start().ok(),
+        false => None,
+    };
+    // Pausing when the audio device is gone is optional since it requires PulseAudio/PipeWire
+    #[cfg(target_os = "linux")]
+    let sink_watcher = match app.config.pause_on_sink_removal {
+        true => sink::SinkWatcher::start().ok(),
+        false => None,
+    };
+    #[cfg(not(target_os = "linux"))]
+    if app.config.pause_on_sleep || app.config.pause_on_sink_removal {
+        log::warn!(
+            "Pausing on suspend or when the audio device is gone is only supported on Linux"
+        );
+    }
+    // The control API is optional since it has to be compiled in (and is stopped when dropped)
+    #[cfg(feature = "http")]
+    let http_server = app.config.http_server.as_ref().and_then(|settings| {
+        http::Server::start(settings)
+            .map_err(|e| log::error!("Could not start the HTTP server: {}", e))
+            .ok()
+    });
+    #[cfg(not(feature = "http"))]
+    if app.config.http_server.is_some() {
+        log::warn!("The HTTP server requires building with the `http` feature");
+    }
+    // Resume only if playback was paused because of the device
+    #[cfg(target_os = "linux")]
+    let mut is_paused_for_sink = false;
+    // Likewise for the focus (not every terminal reports it)
+    let mut is_paused_for_focus = false;
+    // Which properties can be observed depends on the player only
+    let observer = observer::PropertyObserver::start();
+    // Fetched in the background so that a slow server doesn't hold up the start
+    let station_refresh = app
+        .config
+        .stations_url
+        .clone()
+        .map(config::refresh_stations);
+    let mut next_poll = Instant::now();
+    loop {
+        let now = Instant::now();
+        if app
+            .confirm_quit_until
+            .is_some_and(|deadline| deadline <= now)
+        {
+            app.confirm_quit_until = None;
+        }
+        if app.error.as_ref().is_some_and(|(_, expiry)| *expiry <= now) {
+            app.error = None;
+        }
+        if app.toast.as_ref().is_some_and(|(_, expiry)| *expiry <= now) {
+            app.toast = None;
+        }
+        if let Err(e) = app.check_sleep_timer() {
+            app.show_error(e);
+        }
+        if let Err(e) = app.apply_station_switch() {
+            app.show_error(e);
+        }
+        if let Some(stations) = station_refresh
+            .as_ref()
+            .and_then(|receiver| receiver.try_recv().ok())
+        {
+            app.set_stations(stations);
+        }
+
+        // Apply what changed on the player's side right away
+        for change in observer.changes() {
+            match change {
+                observer::Change::Pause(is_paused) => app.is_paused = is_paused,
+                observer::Change::Volume(volume) => app.volume = volume,
+                observer::Change::Other => next_poll = Instant::now(),
+            }
+        }
+
+        // Debounce updates and be easy on the IO
+        if next_poll <= Instant::now() {
+            // Synchronize app state with mpv (and perhaps start mpv if it's not running)
+            app.update();
+            app.marquee_offset += 1;
+            app.spinner_frame += 1;
+            next_poll = Instant::now() + app.config.polling_rate();
+        }
+
+        // Handle media keys and publish changes made by either side
+        if let Some(mpris) = &mpris {
+            for command in mpris.commands() {
+                if let Err(e) = handle_mpris_command(app, command) {
+                    app.show_error(e);
+                }
+            }
+            mpris.update(app).ok();
+        }
+
+        #[cfg(feature = "http")]
+        if let Some(http_server) = &http_server {
+            for command in http_server.commands() {
+                if let Err(e) = handle_http_command(app, command) {
+                    app.show_error(e);
+                }
+            }
+            if let Ok(status) = serde_json::to_string(&Status::new(app)) {
+                http_server.update(status);
+            }
+        }
+
+        // The stream is likely stale after waking up
+        #[cfg(target_os = "linux")]
+        if let Some(sleep_watcher) = &sleep_watcher {
+            for event in sleep_watcher.events() {
+                match event {
+                    sleep::Event::Suspending => app.is_paused = true,
+                    sleep::Event::Resumed if !app.is_stopped => {
+                        if let Err(e) = app.ensure_playing() {
+                            app.show_error(e);
+                        }
+                    }
+                    sleep::Event::Resumed => {} // Stopping is only undone on request
+                }
+            }
+        }
+
+        #[cfg(target_os = "linux")]
+        if let Some(sink_watcher) = &sink_watcher {
+            for event in sink_watcher.events() {
+                let result = match event {
+                    sink::Event::Removed if app.is_playing() => {
+                        is_paused_for_sink = true;
+                        app.toggle_pause()
+                    }
+                    sink::Event::Returned if is_paused_for_sink && app.is_paused => {
+                        is_paused_for_sink = false;
+                        app.toggle_pause()
+                    }
+                    _ => Ok(()),
+                };
+                if let Err(e) = result {
+                    app.show_error(e);
+                }
+            }
+        }
+
+        // Update the UI
+        terminal.draw(|f| ui(f, app))?;
+        #[cfg(feature = "logo")]
+        app.logo_panel.flush()?;
+
+        // Only now, so the toast is shown while waiting for the new player
+        if app.reconnect_pending {
+            if let Err(e) = app.reconnect() {
+                app.show_error(e);
+            }
+            continue;
+        }
+
+        // Wait for input until the next poll (or station switch) is due,
+        // so the UI keeps refreshing while idle
+        let wakeup = app
+            .station_switch_deadline
+            .map_or(next_poll, |deadline| deadline.min(next_poll))
+            .min(Instant::now() + OBSERVER_LATENCY);
+        if !event::poll(wakeup.saturating_duration_since(Instant::now()))? {
+            continue;
+        }
+
+        // Handle events (failed actions are reported without quitting)
+        match event::read()? {
+            Event::Key(key) => match handle_key(app, key) {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(e) => app.show_error(e),
+            },
+            Event::Mouse(mouse) => {
+                if let Err(e) = handle_mouse(app, mouse, terminal.size()?) {
+                    app.show_error(e);
+                }
+            }
+            Event::FocusLost if app.config.pause_on_focus_loss && app.is_playing() => {
+                is_paused_for_focus = true;
+                if let Err(e) = app.toggle_pause() {
+                    app.show_error(e);
+                }
+            }
+            Event::FocusGained if is_paused_for_focus => {
+                is_paused_for_focus = false;
+                if app.is_paused {
+                    if let Err(e) = app.toggle_pause() {
+                        app.show_error(e);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Control the player started by another instance
+fn run_command(command: cli::Command) -> Result<()> {
+    // Loading the config first since it might move the socket
+    // (but without scrobbling or the metadata feed, this is not a new listener)
+    let mut app = App::load_state();
+    if let Some((config_error, _)) = &app.error {
+        eprintln!("Warning: {}", config_error);
+    }
+    let is_paused: bool =
+        mpv_get_property("pause").map_err(|_| "No running player found (start nightride first)")?;
+    app.is_paused = is_paused;
+    match command {
+        cli::Command::Play | cli::Command::Pause | cli::Command::Toggle => {
+            app.is_paused = match command {
+                cli::Command::Play => false,
+                cli::Command::Pause => true,
+                _ => !is_paused,
+            };
+            mpv_set_property("pause", app.is_paused)?;
+        }
+        // There is nothing to debounce here
+        cli::Command::Next => {
+            app.sync_station()?;
+            app.next_station();
+            app.ensure_playing()?;
+        }
+        cli::Command::Previous => {
+            app.sync_station()?;
+            app.previous_station();
+            app.ensure_playing()?;
+        }
+        cli::Command::Volume { volume } => {
+            app.sync_station()?;
+            match volume {
+                cli::VolumeChange::By(change) => app.change_volume(change)?,
+                cli::VolumeChange::To(volume) => app.set_volume(volume)?,
+            }
+        }
+        cli::Command::NowPlaying {
+            format,
+            placeholder,
+        } => {
+            match get_track_info(&app.config.title_separators) {
+                Ok(track) => println!("{}", track.format(&format)),
+                Err(_) => {
+                    println!("{}", placeholder);
+                    std::process::exit(2);
+                }
+            }
+            // Nothing changed, so there is nothing to store
+            return Ok(());
+        }
+    }
+    // Keep the state in sync for the next start of the TUI
+    app.store()
+}
+
+/// What `--json-status` prints (and the HTTP server serves)
+#[derive(Serialize)]
+struct Status<'a> {
+    station: &'a str,
+    is_paused: bool,
+    volume: f32,
+    track: Option<&'a Track>,
+}
+
+impl<'a> Status<'a> {
+    fn new(app: &'a App) -> Self {
+        Self {
+            station: &app.config.stations[app.station].name,
+            is_paused: app.is_paused,
+            volume: app.volume,
+            track: app.current_track.as_ref(),
+        }
+    }
+}
+
+/// Keep printing the state until the reader goes away
+fn print_status(app: &mut App) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    loop {
+        app.update();
+        let line = serde_json::to_string(&Status::new(app))?;
+        // Flush right away since the output is usually piped
+        if writeln!(stdout, "{}", line)
+            .and_then(|_| stdout.flush())
+            .is_err()
+        {
+            return Ok(());
+        }
+        std::thread::sleep(app.config.polling_rate());
+    }
+}
+
+/// Puts the terminal into the state needed by the TUI and restores it when dropped
+/// (even if the app fails or panics)
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        enable_raw_mode()?;
+        // From here on, dropping the guard undoes whatever succeeded
+        let guard = Self;
+        execute!(
+            io::stdout(),
+            EnterAlternateScreen,
+            EnableMouseCapture,
+            EnableFocusChange
+        )?;
+        Ok(guard)
+    }
+
+    fn restore() {
+        #[cfg(feature = "logo")]
+        if logo::is_supported() {
+            logo::clear(&mut io::stdout()).ok();
+        }
+        disable_raw_mode().ok();
+        execute!(
+            io::stdout(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            DisableFocusChange,
+            crossterm::cursor::Show
+        )
+        .ok();
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        Self::restore();
+    }
+}
+
+/// Make sure only one instance is running, since they'd overwrite each other's state on exit
+/// Returns `None` if another instance holds the lock (which is released when the file is dropped)
+fn lock_instance() -> Result<Option<File>> {
+    let file = File::create(platform::runtime_dir().join(LOCK_FILE_NAME))?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(e)) => Err(e.into()),
+    }
+}
+
+fn main() -> Result<()> {
+    let cli = cli::Cli::parse();
+    // Logging is only a debugging aid, so don't refuse to start without it
+    logger::init(cli.verbose).ok();
+    if let Some(command) = cli.command {
+        if let Err(e) = run_command(command) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if cli.list_stations {
+        let (config, config_error) = Config::load_or_default();
+        if let Some(config_error) = config_error {
+            eprintln!("Warning: {}", config_error);
+        }
+        for (i, station) in config.stations.iter().enumerate() {
+            let title = station.title.as_ref().unwrap_or(&station.name);
+            println!("{}\t{}\t{}", i + 1, station.name, title);
+        }
+        return Ok(());
+    }
+
+    // Fail early (before messing with the terminal) if the environment is incomplete
+    // An invalid config is reported once the app is loaded
+    let config = Config::load().unwrap_or_default();
+    let player_command = config.player_command.as_str();
+    let missing_commands: Vec<_> = platform::Native::REQUIRED_COMMANDS
+        .iter()
+        .map(|&(command, hint)| match command {
+            "mpv" => (player_command, hint),
+            _ => (command, hint),
+        })
+        .filter(|(command, _)| !platform::Native::command_exists(command))
+        .collect();
+    if !missing_commands.is_empty() {
+        for (command, hint) in missing_commands {
+            eprintln!("Missing dependency `{}`: {}", command, hint);
+        }
+        std::process::exit(1);
+    }
+    // Wrappers are fine as long as they pass the arguments on to mpv
+    if !player_command.contains("mpv") {
+        log::warn!("The player `{}` might not be mpv", player_command);
+        eprintln!(
+            "Warning: `{}` needs to support mpv's IPC (`--input-ipc-server`) to be controlled",
+            player_command
+        );
+    }
+
+    // Not being able to lock (e.g. on exotic file systems) shouldn't keep the app from starting
+    let lock = match lock_instance() {
+        Ok(None) => {
+            eprintln!(
+                "Nightride is already running (use `nightride --help` to see how to control it)"
+            );
+            std::process::exit(1);
+        }
+        Ok(Some(lock)) => Some(lock),
+        Err(_) => None,
+    };
+
+    let mut app = App::load();
+    // Also shown in the TUI, but this stays visible after quitting (or without the TUI)
+    if let Some((config_error, _)) = &app.error {
+        eprintln!("Warning: {}", config_error);
+    }
+    // Command line arguments override the state from the last session
+    if let Some(station) = &cli.station {
+        app.station = cli::resolve_station(&app.config, station).unwrap_or_else(|e| {
+            cli::Cli::command()
+                .error(clap::error::ErrorKind::InvalidValue, e)
+                .exit()
+        });
+    }
+    if let Some(volume) = cli.volume {
+        app.volume = volume;
+    }
+    if cli.compact {
+        app.compact = true;
+    }
+    if cli.no_ui {
+        // Launching in the background is meant to start playback
+        app.is_paused = false;
+    }
+    app.start();
+    if cli.no_ui {
+        return app.store();
+    }
+    if cli.json_status {
+        print_status(&mut app)?;
+        return app.store();
+    }
+
+    // Panic messages would end up on the alternate screen (and vanish) unless it's left first
+    let default_panic_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        TerminalGuard::restore();
+        default_panic_hook(info);
+    }));
+    let res = TerminalGuard::enter().and_then(|_guard| {
+        let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+        run_app(&mut terminal, &mut app)
+    });
+    // The terminal is restored at this point, so errors can be printed
+    if let Err(e) = &res {
+        eprintln!("Error: {}", e);
+    }
+    // Failing to clean up must not hide why the app stopped
+    if let Err(e) = app.stop_recording() {
+        log::error!("Could not stop recording: {}", e);
+        eprintln!("Could not stop recording: {}", e);
+    }
+    if let Err(e) = app.store() {
+        log::error!("Could not store the state: {}", e);
+        eprintln!("Could not store the state: {}", e);
+    }
+    drop(lock);
+    if res.is_err() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(title: &str, artist: &str, album: &str) -> Track {
+        Track {
+            title: title.into(),
+            artist: artist.into(),
+            album: album.into(),
+        }
+    }
+
+    #[test]
+    fn last_metadata_value_skips_empty_segments() {
+        assert_eq!(last_metadata_value("A;B;"), "B");
+        assert_eq!(last_metadata_value(";"), ";");
+        assert_eq!(last_metadata_value("Solo"), "Solo");
+    }
+
+    #[test]
+    fn track_display() {
+        assert_eq!(
+            track("Nightcall", "Kavinsky", "OutRun").to_string(),
+            "Nightcall by Kavinsky (OutRun)"
+        );
+        assert_eq!(
+            track("Nightcall", "Kavinsky", "").to_string(),
+            "Nightcall by Kavinsky"
+        );
+        assert_eq!(
+            track("夜の街 🌃", "Ünïcødé", "Ⓐlbum").to_string(),
+            "夜の街 🌃 by Ünïcødé (Ⓐlbum)"
+        );
+    }
+
+    #[test]
+    fn search_url_is_percent_encoded() {
+        assert_eq!(
+            track("Rock & Roll #1", "Artist", "").search_url(SearchService::YouTubeMusic),
+            "https://music.youtube.com/search?q=Rock%20%26%20Roll%20%231%20Artist"
+        );
+    }
+
+    #[test]
+    fn parse_track_splits_combined_titles() {
+        let separators = Config::default().title_separators;
+        assert_eq!(
+            parse_track(track("Artist - Song", "", ""), &separators),
+            track("Song", "Artist", "")
+        );
+        assert_eq!(
+            parse_track(track("Song", "", ""), &separators),
+            track("Song", "", "")
+        );
+        assert_eq!(
+            parse_track(track("Song - Remix", "Artist", "Album"), &separators),
+            track("Song - Remix", "Artist", "Album")
+        );
+    }
+
+    #[test]
+    fn read_reply_skips_events() {
+        let response = "{\"event\":\"metadata-update\"}\n{\"data\":42,\"error\":\"success\"}\n";
+        let reply: MpvProperty<u32> = read_reply(response.as_bytes()).unwrap();
+        assert_eq!(reply.data, Some(42));
+        assert_eq!(reply.error, "success");
+        assert!(read_reply::<u32>("{\"event\":\"idle\"}\n".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn clamp_stations_resets_out_of_range_station() {
+        let mut app = App {
+            station: 99,
+            last_station: Some(99),
+            ..App::default()
+        };
+        app.clamp_stations();
+        assert_eq!(app.station, 0);
+        assert_eq!(app.last_station, None);
+    }
+
+    #[test]
+    fn player_that_keeps_dying_counts_as_stalled() {
+        let mut app = App {
+            ipc_failures: IPC_FAILURE_THRESHOLD,
+            next_restart: Some(Instant::now()),
+            ..App::default()
+        };
+        app.config.dead_station_timeout_s = Some(3600);
+        app.skip_dead_station(false);
+        let (since, station) = app.stalled_since.unwrap();
+        assert_eq!(station, 0);
+
+        app.skip_dead_station(false);
+        assert_eq!(app.stalled_since.unwrap().0, since);
+
+        app.station = 1;
+        app.skip_dead_station(false);
+        assert_eq!(app.stalled_since.unwrap().1, 1);
+
+        app.ipc_failures = 0;
+        app.skip_dead_station(false);
+        assert_eq!(app.stalled_since, None);
+    }
+
+    #[test]
+    fn help_scrolls_to_the_last_keybinding() {
+        let mut terminal = Terminal::new(tui::backend::TestBackend::new(80, 24)).unwrap();
+        let (keymap, theme) = (Keymap::default(), Theme::default());
+        let mut scroll = u16::MAX;
+        terminal
+            .draw(|f| scroll = help_popup(f, &keymap, scroll, &theme))
+            .unwrap();
+        assert_eq!(scroll, KEYBINDINGS.len() as u16 + 2 - 24);
+        let buffer = terminal.backend().buffer();
+        let last_line: String = (0..80).map(|x| buffer.get(x, 22).symbol.as_str()).collect();
+        assert!(
+            last_line.contains("Quit and stop playback"),
+            "{}",
+            last_line
+        );
+    }
+
+    #[test]
+    fn apply_config_keeps_the_station_by_name() {
+        let mut app = App {
+            station: 2,
+            last_station: Some(0),
+            ..App::default()
+        };
+        let name = app.config.stations[2].name.clone();
+        let mut config = Config::default();
+        config.stations.remove(0);
+        config.stations.reverse();
+        app.apply_config(config);
+        assert_eq!(app.config.stations[app.station].name, name);
+        assert_eq!(app.last_station, None);
+    }
+
+    #[test]
+    fn quit_confirmation_shows_the_mapped_key() {
+        let mut app = App {
+            confirm_quit_until: Some(Instant::now()),
+            ..App::default()
+        };
+        app.config.keymap.quit = Some('z');
+        assert_eq!(status_line(&app).unwrap().content, "Press z again to quit");
+    }
+
+    #[test]
+    fn layout_fits_tiny_terminals() {
+        for (width, height) in [(20, 5), (0, 0), (1, 1), (3, 2)] {
+            let area = Rect::new(0, 0, width, height);
+            let chunks = layout(area);
+            assert_eq!(chunks.len(), 6);
+            for chunk in chunks.iter() {
+                assert!(
+                    chunk.right() <= area.right() && chunk.bottom() <= area.bottom(),
+                    "{:?} is outside of {:?}",
+                    chunk,
+                    area
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn track_line_truncates_long_albums() {
+        let line = track_line(&track("Song", "Artist", &"a".repeat(200)), 40);
+        assert_eq!(line.chars().count(), 40);
+        assert!(line.starts_with("Song by Artist (a"));
+        assert!(line.ends_with("…)"));
+
+        let line = track_line(&track("Song", "Artist", &"夜🌃".repeat(100)), 40);
+        assert_eq!(line.chars().count(), 40);
+        assert!(line.ends_with("…)"));
+        assert_eq!(truncate("夜🌃夜", 2), "夜…");
+    }
+
+    #[test]
+    fn state_path_without_home() {
+        assert_eq!(App::path(None, None), None);
+        assert_eq!(
+            App::path(None, Some("/data".into())),
+            Some(PathBuf::from("/data/nightride/app.json"))
+        );
+        assert_eq!(
+            App::path(Some("/home/user".into()), Some("/data".into())),
+            Some(PathBuf::from("/home/user/.local/share/nightride/app.json"))
+        );
     }
-    app.store()?;
-    Ok(())
 }
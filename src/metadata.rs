@@ -0,0 +1,104 @@
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    io::{BufRead, BufReader},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use crate::{Result, Track};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// A now-playing entry as sent by the feed
+#[derive(Deserialize)]
+struct Entry {
+    station: String,
+    title: String,
+    artist: String,
+    #[serde(default)]
+    album: String,
+    /// URL of the album art (if the feed sends one)
+    #[serde(default, alias = "artwork", alias = "image")]
+    art: Option<String>,
+}
+
+/// Events contain either a single entry or a list of them (one per station)
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Event {
+    Single(Entry),
+    Multiple(Vec<Entry>),
+}
+
+/// Now-playing information received from a server-sent events feed
+/// (e.g. https://nightride.fm/meta) in the background
+pub struct Feed {
+    tracks: Arc<Mutex<HashMap<String, NowPlaying>>>,
+}
+
+struct NowPlaying {
+    track: Track,
+    art: Option<String>,
+}
+
+impl Feed {
+    pub fn subscribe(url: String) -> Self {
+        let tracks = Arc::new(Mutex::new(HashMap::new()));
+        let feed_tracks = tracks.clone();
+        std::thread::spawn(move || loop {
+            if let Err(e) = listen(&url, &feed_tracks) {
+                log::warn!("Metadata feed disconnected: {}", e);
+            }
+            // Don't serve stale data while disconnected
+            feed_tracks.lock().unwrap().clear();
+            std::thread::sleep(RECONNECT_DELAY);
+        });
+        Self { tracks }
+    }
+
+    /// The current track of the station (if known)
+    pub fn track(&self, station: &str) -> Option<Track> {
+        let tracks = self.tracks.lock().unwrap();
+        tracks
+            .get(station)
+            .map(|now_playing| now_playing.track.clone())
+    }
+
+    /// The album art URL of the current track of the station (if known)
+    #[cfg_attr(not(feature = "logo"), allow(dead_code))]
+    pub fn art(&self, station: &str) -> Option<String> {
+        let tracks = self.tracks.lock().unwrap();
+        tracks.get(station)?.art.clone()
+    }
+}
+
+/// Read events from the feed until the connection fails
+fn listen(url: &str, tracks: &Mutex<HashMap<String, NowPlaying>>) -> Result<()> {
+    let body = ureq::get(url).call()?.into_body().into_reader();
+    for line in BufReader::new(body).lines() {
+        let line = line?;
+        let Some(data) = line.strip_prefix("data:") else {
+            continue; // Comments, keep-alives, event names, ...
+        };
+        let entries = match serde_json::from_str(data.trim()) {
+            Ok(Event::Single(entry)) => vec![entry],
+            Ok(Event::Multiple(entries)) => entries,
+            Err(e) => {
+                log::debug!("Ignoring unparsable metadata event: {}", e);
+                continue;
+            }
+        };
+        let mut tracks = tracks.lock().unwrap();
+        for entry in entries {
+            let track = Track {
+                title: entry.title,
+                artist: entry.artist,
+                album: entry.album,
+            };
+            let art = entry.art.filter(|art| !art.is_empty());
+            tracks.insert(entry.station, NowPlaying { track, art });
+        }
+    }
+    Ok(())
+}
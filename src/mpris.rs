@@ -0,0 +1,268 @@
+use std::{
+    collections::HashMap,
+    sync::mpsc::{self, Receiver, Sender},
+};
+use zbus::{
+    blocking::{connection, Connection},
+    interface,
+    zvariant::{ObjectPath, Value},
+};
+
+use crate::{App, Result, Track};
+
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.nightride";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const TRACK_ID: &str = "/org/mpris/MediaPlayer2/nightride/track";
+
+/// Playback commands received via D-Bus
+pub enum Command {
+    Play,
+    Pause,
+    PlayPause,
+    Next,
+    Previous,
+}
+
+/// The state published via D-Bus
+#[derive(Clone, Default, PartialEq)]
+struct State {
+    is_paused: bool,
+    is_stopped: bool,
+    volume: f32,
+    track: Option<Track>,
+}
+
+/// The `org.mpris.MediaPlayer2` interface
+struct MediaPlayer;
+
+#[interface(name = "org.mpris.MediaPlayer2")]
+impl MediaPlayer {
+    fn raise(&self) {}
+
+    fn quit(&self) {}
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        "Nightride FM".into()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        vec![]
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        vec![]
+    }
+}
+
+/// The `org.mpris.MediaPlayer2.Player` interface
+struct Player {
+    commands: Sender<Command>,
+    state: State,
+}
+
+impl Player {
+    fn send(&self, command: Command) {
+        // The receiver only goes away when the app is quitting
+        self.commands.send(command).ok();
+    }
+}
+
+#[interface(name = "org.mpris.MediaPlayer2.Player")]
+impl Player {
+    fn play(&self) {
+        self.send(Command::Play);
+    }
+
+    fn pause(&self) {
+        self.send(Command::Pause);
+    }
+
+    fn play_pause(&self) {
+        self.send(Command::PlayPause);
+    }
+
+    /// Live radio can't be stopped without quitting, so pause instead
+    fn stop(&self) {
+        self.send(Command::Pause);
+    }
+
+    fn next(&self) {
+        self.send(Command::Next);
+    }
+
+    fn previous(&self) {
+        self.send(Command::Previous);
+    }
+
+    fn seek(&self, _offset: i64) {}
+
+    fn set_position(&self, _track_id: ObjectPath<'_>, _position: i64) {}
+
+    fn open_uri(&self, _uri: String) {}
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        match (self.state.is_stopped, self.state.is_paused) {
+            (true, _) => "Stopped".into(),
+            (false, true) => "Paused".into(),
+            (false, false) => "Playing".into(),
+        }
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        1.0
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, Value<'static>> {
+        let mut metadata = HashMap::from([(
+            "mpris:trackid".to_string(),
+            Value::from(ObjectPath::from_static_str_unchecked(TRACK_ID)),
+        )]);
+        if let Some(track) = &self.state.track {
+            metadata.insert("xesam:title".into(), Value::from(track.title.clone()));
+            metadata.insert(
+                "xesam:artist".into(),
+                Value::from(vec![track.artist.clone()]),
+            );
+            metadata.insert("xesam:album".into(), Value::from(track.album.clone()));
+        }
+        metadata
+    }
+
+    /// MPRIS volume is linear with 1.0 being "normal" volume
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        self.state.volume as f64 / 100.0
+    }
+
+    #[zbus(property)]
+    fn position(&self) -> i64 {
+        0
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+/// MPRIS D-Bus service so desktop media keys and widgets can control playback
+pub struct Mpris {
+    connection: Connection,
+    commands: Receiver<Command>,
+}
+
+impl Mpris {
+    /// Register the service on the session bus
+    pub fn start() -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let player = Player {
+            commands: sender,
+            state: State::default(),
+        };
+        let connection = connection::Builder::session()?
+            .name(BUS_NAME)?
+            .serve_at(OBJECT_PATH, MediaPlayer)?
+            .serve_at(OBJECT_PATH, player)?
+            .build()?;
+        Ok(Self {
+            connection,
+            commands: receiver,
+        })
+    }
+
+    /// Drain the commands received since the last call
+    pub fn commands(&self) -> impl Iterator<Item = Command> + '_ {
+        self.commands.try_iter()
+    }
+
+    /// Publish the app state, notifying clients about changes
+    pub fn update(&self, app: &App) -> Result<()> {
+        let state = State {
+            is_paused: app.is_paused,
+            is_stopped: app.is_stopped,
+            volume: app.volume,
+            track: app.current_track.clone(),
+        };
+        let player_ref = self
+            .connection
+            .object_server()
+            .interface::<_, Player>(OBJECT_PATH)?;
+        let mut player = player_ref.get_mut();
+        if player.state == state {
+            return Ok(());
+        }
+        let previous = std::mem::replace(&mut player.state, state);
+        let emitter = player_ref.signal_emitter();
+        zbus::block_on(async {
+            if (previous.is_paused, previous.is_stopped)
+                != (player.state.is_paused, player.state.is_stopped)
+            {
+                player.playback_status_changed(emitter).await?;
+            }
+            if previous.volume != player.state.volume {
+                player.volume_changed(emitter).await?;
+            }
+            if previous.track != player.state.track {
+                player.metadata_changed(emitter).await?;
+            }
+            Ok::<_, zbus::Error>(())
+        })?;
+        Ok(())
+    }
+}
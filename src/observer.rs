@@ -0,0 +1,91 @@
+use serde::Deserialize;
+use std::{
+    io::{BufRead, BufReader, Write},
+    sync::mpsc::{self, Receiver, Sender},
+    time::Duration,
+};
+
+use crate::{
+    platform::{self, Platform},
+    Result,
+};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+/// Properties whose changes are reported
+const OBSERVED_PROPERTIES: [&str; 6] = [
+    "pause",
+    "volume",
+    "metadata",
+    "path",
+    "paused-for-cache",
+    "core-idle",
+];
+
+/// A property of the player that changed
+pub enum Change {
+    Pause(bool),
+    Volume(f32),
+    /// Anything else (the full state needs to be synced)
+    Other,
+}
+
+/// Messages sent by mpv on its own (replies to commands are ignored)
+#[derive(Deserialize)]
+struct Message {
+    event: Option<String>,
+    name: Option<String>,
+    data: Option<serde_json::Value>,
+}
+
+/// Subscribes to property changes of the player in the background,
+/// so they show up right away instead of on the next poll
+pub struct PropertyObserver {
+    changes: Receiver<Change>,
+}
+
+impl PropertyObserver {
+    pub fn start() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || loop {
+            // Only stop once nobody is listening anymore
+            if let Err(e) = observe(&sender) {
+                if e.is::<mpsc::SendError<Change>>() {
+                    break;
+                }
+            }
+            std::thread::sleep(RECONNECT_DELAY);
+        });
+        Self { changes: receiver }
+    }
+
+    /// Drain the changes received since the last call
+    pub fn changes(&self) -> impl Iterator<Item = Change> + '_ {
+        self.changes.try_iter()
+    }
+}
+
+/// Forward changes until the connection to the player is lost
+fn observe(sender: &Sender<Change>) -> Result<()> {
+    // Events can take arbitrarily long to arrive, so reads mustn't time out
+    let mut stream = platform::Native::connect(None)?;
+    for (id, property) in OBSERVED_PROPERTIES.iter().enumerate() {
+        let command = serde_json::json!({ "command": ["observe_property", id, property] });
+        stream.write_all(format!("{}\n", command).as_bytes())?;
+    }
+    for line in BufReader::new(stream).lines() {
+        let Ok(message) = serde_json::from_str::<Message>(&line?) else {
+            continue;
+        };
+        if message.event.as_deref() != Some("property-change") {
+            continue;
+        }
+        let data = message.data.unwrap_or_default();
+        let change = match (message.name.as_deref(), data.as_bool(), data.as_f64()) {
+            (Some("pause"), Some(is_paused), _) => Change::Pause(is_paused),
+            (Some("volume"), _, Some(volume)) => Change::Volume(volume as f32),
+            _ => Change::Other,
+        };
+        sender.send(change)?;
+    }
+    Ok(())
+}
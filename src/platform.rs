@@ -0,0 +1,188 @@
+use std::{
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use crate::Result;
+
+/// A connection to the player's IPC endpoint
+pub trait IpcStream: Read + Write {}
+
+impl<T: Read + Write> IpcStream for T {}
+
+/// Platform-specific process management and IPC
+pub trait Platform {
+    /// External commands required at runtime along with a hint on how to get them
+    const REQUIRED_COMMANDS: &'static [(&'static str, &'static str)];
+
+    /// Where the player's IPC server listens unless configured otherwise
+    fn default_ipc_endpoint() -> String;
+
+    /// Connect to the player's IPC server (with reads timing out after `timeout`)
+    fn connect(timeout: Option<Duration>) -> Result<Box<dyn IpcStream>>;
+
+    /// Start the player in the background, so it outlives the terminal
+    fn spawn_detached(program: &str, args: &[&str]) -> Result<()>;
+
+    fn kill(pid: u32) -> Result<()>;
+
+    fn is_executable(path: &Path) -> bool;
+
+    /// Check whether an executable with the given name can be found on the `$PATH`
+    fn command_exists(name: &str) -> bool {
+        std::env::var_os("PATH")
+            .map(|paths| {
+                std::env::split_paths(&paths).any(|dir| Self::is_executable(&dir.join(name)))
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// Where to put per-user runtime files (falling back to the temp directory, e.g. `/tmp`)
+pub fn runtime_dir() -> PathBuf {
+    std::env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(std::env::temp_dir)
+}
+
+static IPC_ENDPOINT: OnceLock<String> = OnceLock::new();
+
+/// Use a custom IPC endpoint (only possible before it is first used)
+pub fn set_ipc_endpoint(endpoint: String) {
+    IPC_ENDPOINT.set(endpoint).ok();
+}
+
+/// Where the player's IPC server listens (passed to mpv's `--input-ipc-server`)
+pub fn ipc_endpoint() -> &'static str {
+    IPC_ENDPOINT.get_or_init(Native::default_ipc_endpoint)
+}
+
+#[cfg(unix)]
+pub type Native = Unix;
+#[cfg(windows)]
+pub type Native = Windows;
+
+#[cfg(unix)]
+pub struct Unix;
+
+#[cfg(unix)]
+impl Platform for Unix {
+    const REQUIRED_COMMANDS: &'static [(&'static str, &'static str)] = &[
+        (
+            "mpv",
+            "install it using your package manager (e.g. `sudo apt install mpv`)",
+        ),
+        ("nohup", "it is part of GNU coreutils"),
+        ("kill", "it is part of procps/util-linux"),
+    ];
+
+    /// Prefer the per-user runtime directory so multiple users don't share a socket
+    fn default_ipc_endpoint() -> String {
+        runtime_dir().join("nightride.sock").display().to_string()
+    }
+
+    fn connect(timeout: Option<Duration>) -> Result<Box<dyn IpcStream>> {
+        let stream = std::os::unix::net::UnixStream::connect(ipc_endpoint())?;
+        stream.set_read_timeout(timeout)?;
+        Ok(Box::new(stream))
+    }
+
+    fn spawn_detached(program: &str, args: &[&str]) -> Result<()> {
+        // Use nohup to avoid the process being killed when the terminal is closed
+        Command::new("nohup")
+            .arg(program)
+            .args(args)
+            .args([
+                ">/dev/null", // Do not create nohup.out
+                "2>&1",       // Redirect stderr to stdout
+                "&",          // Run in background
+            ])
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+        Ok(())
+    }
+
+    fn kill(pid: u32) -> Result<()> {
+        Command::new("kill").arg(pid.to_string()).output()?;
+        Ok(())
+    }
+
+    fn is_executable(path: &Path) -> bool {
+        use std::os::unix::fs::PermissionsExt;
+        path.metadata()
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(windows)]
+pub struct Windows;
+
+#[cfg(windows)]
+impl Platform for Windows {
+    const REQUIRED_COMMANDS: &'static [(&'static str, &'static str)] = &[
+        ("mpv", "download it from https://mpv.io/installation/"),
+        ("taskkill", "it is part of Windows"),
+    ];
+
+    fn default_ipc_endpoint() -> String {
+        r"\\.\pipe\nightride".into()
+    }
+
+    /// Named pipes can be opened like regular files (reads can't time out though)
+    fn connect(_timeout: Option<Duration>) -> Result<Box<dyn IpcStream>> {
+        let pipe = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(ipc_endpoint())?;
+        Ok(Box::new(pipe))
+    }
+
+    fn spawn_detached(program: &str, args: &[&str]) -> Result<()> {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x00000008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
+        Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP)
+            .spawn()?;
+        Ok(())
+    }
+
+    fn kill(pid: u32) -> Result<()> {
+        Command::new("taskkill")
+            .args(["/PID", pid.to_string().as_str(), "/F"])
+            .output()?;
+        Ok(())
+    }
+
+    fn is_executable(path: &Path) -> bool {
+        path.with_extension("exe").is_file()
+    }
+}
+
+/// Open the URL with the default application (e.g. the web browser)
+pub fn open_url(url: &str) {
+    let mut command = if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else if cfg!(windows) {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]); // The empty title keeps `start` from eating the URL
+        command
+    } else {
+        Command::new("xdg-open")
+    };
+    command
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok();
+}
@@ -0,0 +1,82 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::{Command, Stdio},
+    sync::mpsc::{self, Receiver},
+};
+
+use crate::Result;
+
+pub enum Event {
+    /// The sink playback started on is gone (e.g. headphones were unplugged)
+    Removed,
+    /// The sink is back
+    Returned,
+}
+
+/// Watches PulseAudio/PipeWire for the sink that was active on startup disappearing,
+/// using `pactl` in the background
+pub struct SinkWatcher {
+    events: Receiver<Event>,
+}
+
+impl SinkWatcher {
+    /// Fails if `pactl` is unavailable or there is no sound server
+    pub fn start() -> Result<Self> {
+        let sink = default_sink()?;
+        let mut subscription = Command::new("pactl")
+            .arg("subscribe")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let output = subscription.stdout.take().ok_or("No output from pactl")?;
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut is_present = true;
+            // Lines look like `Event 'remove' on sink #42`
+            for line in BufReader::new(output).lines().map_while(|line| line.ok()) {
+                if !line.contains(" on sink #") {
+                    continue;
+                }
+                let was_present = is_present;
+                is_present = sink_exists(&sink);
+                let event = match (was_present, is_present) {
+                    (true, false) => Event::Removed,
+                    (false, true) => Event::Returned,
+                    _ => continue,
+                };
+                if sender.send(event).is_err() {
+                    break;
+                }
+            }
+            subscription.kill().ok();
+        });
+        Ok(Self { events: receiver })
+    }
+
+    /// Drain the events received since the last call
+    pub fn events(&self) -> impl Iterator<Item = Event> + '_ {
+        self.events.try_iter()
+    }
+}
+
+fn default_sink() -> Result<String> {
+    let output = Command::new("pactl").arg("get-default-sink").output()?;
+    let sink = String::from_utf8(output.stdout)?.trim().to_string();
+    match output.status.success() && !sink.is_empty() {
+        true => Ok(sink),
+        false => Err("Could not get the default sink".into()),
+    }
+}
+
+fn sink_exists(sink: &str) -> bool {
+    Command::new("pactl")
+        .args(["list", "short", "sinks"])
+        .output()
+        .map(|output| {
+            String::from_utf8_lossy(&output.stdout)
+                .lines()
+                .any(|line| line.split('\t').nth(1) == Some(sink))
+        })
+        .unwrap_or(false)
+}
@@ -0,0 +1,70 @@
+use std::sync::mpsc::{self, Receiver};
+use zbus::{
+    blocking::{Connection, Proxy},
+    zvariant::OwnedFd,
+};
+
+use crate::{mpv_set_property, Result};
+
+pub enum Event {
+    Suspending,
+    Resumed,
+}
+
+/// Listens for systemd-logind's sleep signal in the background
+pub struct SleepWatcher {
+    events: Receiver<Event>,
+}
+
+impl SleepWatcher {
+    /// Subscribe to the signal on the system bus
+    pub fn start() -> Result<Self> {
+        let connection = Connection::system()?;
+        let proxy = Proxy::new(
+            &connection,
+            "org.freedesktop.login1",
+            "/org/freedesktop/login1",
+            "org.freedesktop.login1.Manager",
+        )?;
+        let signals = proxy.receive_signal("PrepareForSleep")?;
+        // Delay suspending until playback is paused (released by dropping it)
+        let inhibit = move || {
+            proxy
+                .call::<_, _, OwnedFd>(
+                    "Inhibit",
+                    &("sleep", "Nightride FM", "Pause playback", "delay"),
+                )
+                .ok()
+        };
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut lock = inhibit();
+            for signal in signals {
+                // The argument tells whether the system is about to suspend or has woken up
+                let event = match signal.body().deserialize::<bool>() {
+                    Ok(true) => {
+                        // Pause right away, the main loop might not get to it in time
+                        mpv_set_property("pause", true).ok();
+                        lock = None;
+                        Event::Suspending
+                    }
+                    Ok(false) => {
+                        lock = inhibit();
+                        Event::Resumed
+                    }
+                    Err(_) => continue,
+                };
+                if sender.send(event).is_err() {
+                    break;
+                }
+            }
+            drop(lock);
+        });
+        Ok(Self { events: receiver })
+    }
+
+    /// Drain the events received since the last call
+    pub fn events(&self) -> impl Iterator<Item = Event> + '_ {
+        self.events.try_iter()
+    }
+}
@@ -0,0 +1,56 @@
+use serde::Deserialize;
+use tui::style::{Color, Style};
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Preset {
+    /// Neon pink and cyan
+    Synthwave,
+    /// No colors at all
+    Mono,
+}
+
+/// UI colors, taken from the preset unless set explicitly
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    pub preset: Option<Preset>,
+    pub border: Option<Color>,
+    pub title: Option<Color>,
+    pub gauge: Option<Color>,
+}
+
+impl Theme {
+    /// Used if the terminal shouldn't get any colors
+    pub const COLORLESS: Theme = Theme {
+        preset: Some(Preset::Mono),
+        border: None,
+        title: None,
+        gauge: None,
+    };
+
+    pub fn border(&self) -> Style {
+        let preset = match self.preset {
+            Some(Preset::Synthwave) => Color::LightMagenta,
+            Some(Preset::Mono) | None => Color::Reset,
+        };
+        Style::default().fg(self.border.unwrap_or(preset))
+    }
+
+    pub fn title(&self) -> Style {
+        let preset = match self.preset {
+            Some(Preset::Synthwave) => Color::LightCyan,
+            Some(Preset::Mono) | None => Color::Reset,
+        };
+        Style::default().fg(self.title.unwrap_or(preset))
+    }
+
+    pub fn gauge(&self) -> Style {
+        let preset = match self.preset {
+            Some(Preset::Synthwave) => Color::LightMagenta,
+            Some(Preset::Mono) => Color::Reset,
+            None => Color::Magenta,
+        };
+        Style::default().fg(self.gauge.unwrap_or(preset))
+    }
+}